@@ -1,150 +1,1076 @@
 use bdl_frontend::ast::{
-    AssignmentExpr, BinOpExpr, Expr, FloatLiteral, FunctionDef, Identifier, IfExpr, IntegerLiteral,
-    ListExpr, MethodCallExpr, PrintExpr, ReassignmentExpr, RepExpr, ReturnExpr, StringLiteral,
-    Type, UnOpExpr,
+    AssignmentExpr, BinOpExpr, DestructureExpr, EnumDef, Expr, FieldAccessExpr, FloatLiteral,
+    ForExpr, FunctionDef, Identifier, IfExpr, IndexExpr, IntegerLiteral, LambdaExpr, ListExpr,
+    MatchExpr, MethodCallExpr, Pattern, PrintExpr, RangeExpr, ReassignmentExpr, RepExpr,
+    ReturnExpr, Span, StructDef, TupleExpr, Type, TypedIdentifier, UnOpExpr, WhileExpr,
 };
 
+use crate::runtime;
 use crustal as CG;
 use std::fmt::Write;
-use std::path::Path;
 
-pub fn generate(ast: &bdl_frontend::ast::Program) -> String {
+/// How `int` arithmetic is lowered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArithMode {
+    /// Arbitrary precision, through `bdl::Int`
+    #[default]
+    BigInt,
+    /// 64-bit, aborting with the source location when `+ - *` overflow
+    Checked,
+    /// 64-bit with the raw C++ operators
+    Release,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub arith: ArithMode,
+    /// Source file named in runtime errors
+    pub file: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            arith: ArithMode::default(),
+            file: "<input>".to_string(),
+        }
+    }
+}
+
+// The C++ name for a BDL name. Every name the program declares gets the
+// `bdl_` prefix, so none of them can meet a C++ keyword, a macro or a name
+// from the runtime or the standard library. Names the generator makes up
+// itself never start with `bdl_`
+fn cpp_name(name: &str) -> String {
+    format!("bdl_{name}")
+}
+
+pub fn generate(ast: &bdl_frontend::ast::Program, options: &Options) -> String {
     // Add standard includes
     let mut scope = CG::Scope::new();
     scope.new_include("bits/stdc++.h", true);
 
-    // The language only generates a singular main function.
-    // Everything is pushed into the main function from here
-    let main_fn = scope.new_function("main", CG::Type::new_int32());
+    // Top-level functions are hoisted out to free functions, everything
+    // else is pushed into the main function
+    let mut declarations = String::new();
+    let mut definitions = String::new();
+    let int = CG::Type::new(CG::BaseType::Class("int".to_string()));
+    let mut main_fn = CG::Function::new("main", int);
     let body = main_fn.body();
+    let mut structs = vec![];
+    let mut enums = vec![];
 
     // Generate code for each expression
     for expr in &ast.expressions {
-        process_expression(body, expr);
+        match expr {
+            Expr::StructDef(def) => structs.push(def),
+            Expr::EnumDef(def) => enums.push(def),
+            Expr::FunctionDef(func) => {
+                let (declaration, definition) = generate_function(options, func);
+                declarations.push_str(&declaration);
+                definitions.push_str(&definition);
+                definitions.push('\n');
+            }
+            _ => generate_statement(body, options, expr),
+        }
+    }
+
+    body.return_expr(CG::Expr::Raw("0".to_string()));
+
+    // Every function is declared up front so that they can call each other
+    // in any order, as they can in BDL
+    if !declarations.is_empty() {
+        declarations.push('\n');
+    }
+    let includes = scope.to_string();
+    let int = match options.arith {
+        ArithMode::BigInt => runtime::INT,
+        ArithMode::Checked | ArithMode::Release => runtime::INT64,
+    };
+    format!(
+        "{}\n\n{}\n{}\n{}\n{}\n{}{declarations}{definitions}{main_fn}",
+        includes.trim_start(),
+        int,
+        runtime::PRELUDE,
+        runtime::LIST,
+        runtime::LOOPS,
+        generate_types(&structs, &enums)
+    )
+}
+
+// Every type is declared first so that fields can hold lists of structs
+// defined after them. Enums only hold a pointer to their variants, so they
+// are complete before any struct, and their variants come after all of the
+// structs. The equality and printing of each come after all of that
+fn generate_types(structs: &[&StructDef], enums: &[&EnumDef]) -> String {
+    if structs.is_empty() && enums.is_empty() {
+        return String::new();
+    }
+    let mut order = vec![];
+    for def in structs {
+        order_struct(def, structs, &mut order);
+    }
+
+    let mut out = String::new();
+    for name in structs
+        .iter()
+        .map(|d| &d.name)
+        .chain(enums.iter().map(|d| &d.name))
+    {
+        writeln!(out, "struct {};", cpp_name(&name.value)).unwrap();
+    }
+    out.push('\n');
+    for def in enums {
+        out.push_str(&generate_enum(def));
+        out.push('\n');
+    }
+    for def in &order {
+        out.push_str(&generate_struct(def));
+        out.push('\n');
+    }
+    for def in enums {
+        for variant in &def.variants {
+            let name = format!(
+                "{}::{}",
+                cpp_name(&def.name.value),
+                cpp_name(&variant.name.value)
+            );
+            writeln!(out, "struct {name} {{").unwrap();
+            for field in &variant.fields {
+                let ty = get_string_type(&field.associated_type);
+                writeln!(out, "    {ty} {};", cpp_name(&field.value.value)).unwrap();
+            }
+            out.push_str("};\n\n");
+        }
+    }
+
+    let mut names = order
+        .iter()
+        .map(|d| cpp_name(&d.name.value))
+        .collect::<Vec<String>>();
+    for def in enums {
+        let name = cpp_name(&def.name.value);
+        for variant in &def.variants {
+            names.push(format!("{name}::{}", cpp_name(&variant.name.value)));
+        }
+        names.push(name);
+    }
+    for name in &names {
+        writeln!(out, "bool operator==(const {name} &a, const {name} &b);").unwrap();
+        writeln!(out, "bool operator!=(const {name} &a, const {name} &b);").unwrap();
+        writeln!(
+            out,
+            "void write_repr(std::ostream &os, const {name} &value);"
+        )
+        .unwrap();
+    }
+    out.push('\n');
+    for def in &order {
+        let label = &def.name.value;
+        out.push_str(&generate_record_functions(
+            &cpp_name(label),
+            label,
+            &def.fields,
+        ));
+    }
+    for def in enums {
+        out.push_str(&generate_enum_functions(def));
+    }
+    out
+}
+
+// Appends `def` to `order` after the structs its fields use
+fn order_struct<'a>(def: &'a StructDef, defs: &[&'a StructDef], order: &mut Vec<&'a StructDef>) {
+    if order.iter().any(|d| d.name.value == def.name.value) {
+        return;
+    }
+    order.push(def);
+    let at = order.len() - 1;
+    for field in &def.fields {
+        for name in struct_names(&field.associated_type) {
+            if let Some(used) = defs.iter().find(|d| d.name.value == name) {
+                order_struct(used, defs, order);
+            }
+        }
+    }
+    // Moved behind everything its fields needed
+    let def = order.remove(at);
+    order.push(def);
+}
+
+fn struct_names(t: &Type) -> Vec<String> {
+    match t {
+        Type::Named(name) => vec![name.clone()],
+        Type::List(t) => struct_names(t),
+        Type::Tuple(ts) => ts.iter().flat_map(struct_names).collect(),
+        _ => vec![],
+    }
+}
+
+// A plain C++ struct, default constructible so that it can be declared
+// before it is assigned, like every other variable
+fn generate_struct(def: &StructDef) -> String {
+    let name = cpp_name(&def.name.value);
+    let mut out = format!("struct {name} {{\n");
+    for field in &def.fields {
+        let ty = get_string_type(&field.associated_type);
+        writeln!(out, "    {ty} {};", cpp_name(&field.value.value)).unwrap();
+    }
+    writeln!(out, "\n    {name}() = default;").unwrap();
+    if !def.fields.is_empty() {
+        let params = def
+            .fields
+            .iter()
+            .map(|f| {
+                let ty = get_string_type(&f.associated_type);
+                format!("{ty} {}", cpp_name(&f.value.value))
+            })
+            .collect::<Vec<String>>();
+        let inits = def
+            .fields
+            .iter()
+            .map(|f| format!("{0}(std::move({0}))", cpp_name(&f.value.value)))
+            .collect::<Vec<String>>();
+        writeln!(
+            out,
+            "    {name}({}) : {} {{}}",
+            params.join(", "),
+            inits.join(", ")
+        )
+        .unwrap();
+    }
+    out.push_str("};\n");
+    out
+}
+
+// Structs compare field by field and print like a Python dataclass. They
+// live next to the struct, where `bdl::print` finds them by argument
+// dependent lookup. Variants without fields print as just their `label`
+fn generate_record_functions(name: &str, label: &str, fields: &[TypedIdentifier]) -> String {
+    let members = |object: &str| {
+        fields
+            .iter()
+            .map(|f| format!("{object}.{}", cpp_name(&f.value.value)))
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    let mut out = String::new();
+    writeln!(
+        out,
+        "bool operator==(const {name} &a, const {name} &b) {{\n    \
+         return std::tie({}) == std::tie({});\n}}\n",
+        members("a"),
+        members("b")
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "bool operator!=(const {name} &a, const {name} &b) {{\n    return !(a == b);\n}}\n"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "void write_repr(std::ostream &os, const {name} &value) {{\n    using bdl::write_repr;"
+    )
+    .unwrap();
+    let mut separator = format!("{label}(");
+    for field in fields {
+        let field = &field.value.value;
+        writeln!(out, "    os << \"{separator}{field}=\";").unwrap();
+        writeln!(out, "    write_repr(os, value.{});", cpp_name(field)).unwrap();
+        separator = ", ".to_string();
+    }
+    // Only the variants of an enum are named inside another type
+    if fields.is_empty() && !name.contains("::") {
+        writeln!(out, "    os << \"{label}()\";").unwrap();
+    } else if fields.is_empty() {
+        writeln!(out, "    os << \"{label}\";").unwrap();
+    } else {
+        out.push_str("    os << ')';\n");
+    }
+    out.push_str("}\n\n");
+    out
+}
+
+// An enum is a pointer to a `std::variant` of one struct per variant. What
+// it points to is never modified, so copies can share it and still behave
+// like values, and a variant can hold the enum it belongs to
+fn generate_enum(def: &EnumDef) -> String {
+    let name = cpp_name(&def.name.value);
+    let mut out = format!("struct {name} {{\n");
+    let variants = def
+        .variants
+        .iter()
+        .map(|v| cpp_name(&v.name.value))
+        .collect::<Vec<String>>();
+    for variant in &variants {
+        writeln!(out, "    struct {variant};").unwrap();
     }
-    main_fn.to_string()
+    writeln!(
+        out,
+        "    using Variant = std::variant<{}>;\n\n    std::shared_ptr<const Variant> value;",
+        variants.join(", ")
+    )
+    .unwrap();
+    out.push_str("};\n");
+    out
 }
 
-enum ExprResult {
-    integer(i64),
-    float(f64),
-    string(String),
-    list(Vec<ExprResult>),
-    none,
+// Equality and printing go through the variant, then each variant gets a
+// constructor named after it. Those without fields are constants instead
+fn generate_enum_functions(def: &EnumDef) -> String {
+    let name = cpp_name(&def.name.value);
+    let mut out = String::new();
+    writeln!(
+        out,
+        "bool operator==(const {name} &a, const {name} &b) {{\n    return *a.value == *b.value;\n}}\n"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "bool operator!=(const {name} &a, const {name} &b) {{\n    return !(a == b);\n}}\n"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "void write_repr(std::ostream &os, const {name} &value) {{\n    \
+         std::visit([&os](const auto &variant) {{ write_repr(os, variant); }}, *value.value);\n}}\n"
+    )
+    .unwrap();
+    for variant in &def.variants {
+        let label = &variant.name.value;
+        out.push_str(&generate_record_functions(
+            &format!("{name}::{}", cpp_name(label)),
+            label,
+            &variant.fields,
+        ));
+    }
+    for variant in &def.variants {
+        let variant_name = cpp_name(&variant.name.value);
+        let args = variant
+            .fields
+            .iter()
+            .map(|f| format!("std::move({})", cpp_name(&f.value.value)))
+            .collect::<Vec<String>>();
+        let value = format!(
+            "{name}{{std::make_shared<const {name}::Variant>({name}::{variant_name}{{{}}})}}",
+            args.join(", ")
+        );
+        if variant.fields.is_empty() {
+            writeln!(out, "const {name} {variant_name} = {value};\n").unwrap();
+        } else {
+            let params = variant
+                .fields
+                .iter()
+                .map(|f| {
+                    let ty = get_string_type(&f.associated_type);
+                    format!("{ty} {}", cpp_name(&f.value.value))
+                })
+                .collect::<Vec<String>>();
+            writeln!(
+                out,
+                "{name} {variant_name}({}) {{\n    return {value};\n}}\n",
+                params.join(", ")
+            )
+            .unwrap();
+        }
+    }
+    out
 }
 
 // optionally returns a CG::Expression
-fn process_expression(context: &mut CG::Block, expr: &Expr) -> Option<CG::Expr> {
+fn process_expression(context: &mut CG::Block, options: &Options, expr: &Expr) -> Option<CG::Expr> {
     match expr {
         // doesn't do anything
-        // TODO: Support different integers
-        Expr::Integer(i) => Some(CG::Expr::new_num(i.value as u64)),
+        Expr::Integer(i) => Some(generate_integer(options, i)),
+        Expr::Float(f) => Some(generate_float(f)),
+        Expr::Boolean(b) => Some(CG::Expr::Raw(b.value.to_string())),
+        // A plain literal would be a `const char *`, which has no `+`
+        Expr::String(s) => Some(CG::Expr::Raw(format!("std::string({:?})", s.value))),
         Expr::AssignmentExpr(assign) => {
-            generate_assignment(context, assign);
+            generate_assignment(context, options, assign);
             None
         }
         Expr::ReassignmentExpr(reassign) => {
-            generate_reassignment(context, reassign);
-            None
-        }
-        Expr::MethodCallExpr(method) => {
-            generate_method_call(context, method);
+            generate_reassignment(context, options, reassign);
             None
         }
+        Expr::MethodCallExpr(method) => Some(generate_method_call(context, options, method)),
         Expr::PrintExpr(print) => {
-            generate_print(context, print);
+            generate_print(context, options, print);
             None
         }
-        Expr::IfExpr(if_expr) => {
-            generate_if(context, if_expr);
+        Expr::IfExpr(if_expr) if if_expr.ty == Type::None => {
+            generate_if(context, options, if_expr);
             None
         }
+        Expr::IfExpr(if_expr) => Some(generate_if_value(options, if_expr)),
         Expr::RepExpr(rep) => {
-            generate_rep(context, rep);
+            generate_rep(context, options, rep);
+            None
+        }
+        Expr::WhileExpr(w) => {
+            generate_while(context, options, w);
+            None
+        }
+        Expr::ForExpr(f) => {
+            generate_for(context, options, f);
+            None
+        }
+        Expr::RangeExpr(range) => Some(generate_range(context, options, range)),
+        Expr::BreakExpr(b) => {
+            generate_jump(context, &b.label, b.target, "break");
+            None
+        }
+        Expr::ContinueExpr(c) => {
+            generate_jump(context, &c.label, c.target, "continue");
+            None
+        }
+        Expr::MatchExpr(m) if m.ty == Type::None => {
+            generate_match(context, options, m, Tail::Discard);
+            None
+        }
+        Expr::MatchExpr(m) => Some(generate_match_value(options, m)),
+        Expr::Identifier(id) => Some(generate_identifier(id)),
+        Expr::ListExpr(list) => Some(generate_list_expr(context, options, list)),
+        Expr::TupleExpr(tuple) => Some(generate_tuple_expr(context, options, tuple)),
+        Expr::FieldAccessExpr(access) => Some(generate_field_access(context, options, access)),
+        Expr::IndexExpr(index) => Some(generate_index(context, options, index)),
+        Expr::DestructureExpr(destructure) => {
+            generate_destructure(context, options, destructure);
+            None
+        }
+        Expr::BinOp(binop) => Some(generate_binop(context, options, binop)),
+        Expr::UnOp(unop) => Some(generate_unop(context, options, unop)),
+        Expr::FunctionDef(func) => {
+            generate_local_functions(context, options, &[func]);
             None
         }
-        Expr::Identifier(id) => Some(generate_identifier(context, id)),
-        Expr::ListExpr(list) => Some(generate_list_expr(context, list)),
-        Expr::BinOp(binop) => Some(generate_binop(context, binop)),
-        Expr::UnOp(unop) => Some(generate_unop(context, unop)),
-        Expr::FunctionDef(func) => todo!(),
-        Expr::ReturnExpr(ret) => todo!(),
-        _ => todo!(),
+        Expr::LambdaExpr(lambda) => Some(generate_lambda(options, lambda)),
+        Expr::ReturnExpr(ret) => {
+            generate_return(context, options, ret);
+            None
+        }
+        // `none` has no value to compute
+        Expr::NoneExpr(_) => None,
+        Expr::StructDef(_) | Expr::EnumDef(_) => {
+            unreachable!("types are only defined at the top level, and emitted before `main`")
+        }
+    }
+}
+
+// Emits `expr` as a statement, discarding its value if it has one
+fn generate_statement(context: &mut CG::Block, options: &Options, expr: &Expr) {
+    match expr {
+        Expr::IfExpr(if_expr) => generate_if(context, options, if_expr),
+        Expr::MatchExpr(m) => generate_match(context, options, m, Tail::Discard),
+        _ => {
+            if let Some(value) = process_expression(context, options, expr) {
+                context.raw_expr(value);
+            }
+        }
+    }
+}
+
+// Emits the expressions of a block. Functions defined in the block can be
+// called from anywhere in it, so they are emitted before everything else
+// Where the value of the last expression of a block goes
+#[derive(Clone, Copy)]
+enum Tail<'a> {
+    Discard,
+    Return,
+    // Into the `std::optional` with this name
+    Store(&'a str),
+}
+
+fn generate_block(context: &mut CG::Block, options: &Options, exprs: &[Expr], tail: Tail) {
+    let funcs = exprs
+        .iter()
+        .filter_map(|expr| match expr {
+            Expr::FunctionDef(func) => Some(func),
+            _ => None,
+        })
+        .collect::<Vec<&FunctionDef>>();
+    if !funcs.is_empty() {
+        generate_local_functions(context, options, &funcs);
+    }
+    let exprs = exprs
+        .iter()
+        .filter(|expr| !matches!(expr, Expr::FunctionDef(_)))
+        .collect::<Vec<&Expr>>();
+    if let Some((last, rest)) = exprs.split_last() {
+        for expr in rest {
+            generate_statement(context, options, expr);
+        }
+        match tail {
+            Tail::Discard => generate_statement(context, options, last),
+            _ => generate_tail(context, options, last, tail),
+        }
+    }
+}
+
+// Emits the last expression of a block, whose value is returned or stored.
+// A `return`, `break` or `continue` there has no value and is emitted as is
+fn generate_tail(context: &mut CG::Block, options: &Options, expr: &Expr, tail: Tail) {
+    match expr {
+        Expr::IfExpr(if_expr) if if_expr.else_block.is_some() => {
+            generate_if_tail(context, options, if_expr, tail)
+        }
+        Expr::MatchExpr(m) => generate_match(context, options, m, tail),
+        _ => {
+            if let Some(value) = process_expression(context, options, expr) {
+                match tail {
+                    Tail::Store(name) => context.raw(format!("{name}.emplace({value})")),
+                    _ => context.return_expr(value),
+                };
+            }
+        }
+    }
+}
+
+// An `if` with an `else`, each branch returning or storing its value
+fn generate_if_tail(context: &mut CG::Block, options: &Options, if_expr: &IfExpr, tail: Tail) {
+    let cond = process_expression(context, options, &if_expr.condition).unwrap();
+    let if_else_expr = context.new_ifelse(&cond);
+    generate_block(
+        if_else_expr.then_branch(),
+        options,
+        &if_expr.then_block,
+        tail,
+    );
+    let else_block = if_expr.else_block.as_ref().unwrap();
+    generate_block(if_else_expr.other_branch(), options, else_block, tail);
+}
+
+fn generate_return(context: &mut CG::Block, options: &Options, ret: &ReturnExpr) {
+    match process_expression(context, options, &ret.value) {
+        Some(value) => context.return_expr(value),
+        None => context.return_none(),
+    };
+}
+
+// The C++ signature of `func`. Type variables left in its parameters become
+// template parameters, and so does every parameter that is itself a
+// function, since C++ has no single type for those
+fn function_signature(func: &FunctionDef) -> (Vec<String>, CG::Type, Vec<(String, CG::Type)>) {
+    let mut templates = vec![];
+    let mut params = vec![];
+    for (i, arg) in func.args.iter().enumerate() {
+        let ty = match &arg.associated_type {
+            Type::FunctionType(..) => {
+                let name = format!("F{i}");
+                templates.push(name.clone());
+                CG::Type::new(CG::BaseType::Class(name))
+            }
+            ty => {
+                for var in type_vars(ty) {
+                    if !templates.contains(&var) {
+                        templates.push(var);
+                    }
+                }
+                get_crustal_type(ty)
+            }
+        };
+        params.push((cpp_name(&arg.value.value), ty));
+    }
+
+    let ret = func.return_type.as_ref().unwrap_or(&Type::None);
+    // A return type built from the parameters' functions is left to C++
+    let ret = if type_vars(ret).iter().all(|var| templates.contains(var)) {
+        get_crustal_type(ret)
+    } else {
+        CG::Type::new(CG::BaseType::Class("auto".to_string()))
+    };
+    (templates, ret, params)
+}
+
+// Names of the template parameters standing for the type variables in `t`
+fn type_vars(t: &Type) -> Vec<String> {
+    match t {
+        Type::Var(v) => vec![format!("T{v}")],
+        Type::List(t) => type_vars(t),
+        Type::Tuple(ts) => ts.iter().flat_map(type_vars).collect(),
+        Type::FunctionType(args, ret) => {
+            let mut vars = args.iter().flat_map(type_vars).collect::<Vec<String>>();
+            vars.extend(ret.as_ref().iter().flat_map(type_vars));
+            vars
+        }
+        _ => vec![],
+    }
+}
+
+// What a function body does with its last value
+fn body_tail(return_type: &Option<Type>) -> Tail<'static> {
+    match return_type {
+        None | Some(Type::None) => Tail::Discard,
+        _ => Tail::Return,
+    }
+}
+
+// A top-level function, as its declaration and its definition
+fn generate_function(options: &Options, func: &FunctionDef) -> (String, String) {
+    let (templates, ret, params) = function_signature(func);
+    let mut function = CG::Function::new(&cpp_name(&func.name.value), ret);
+    for (name, ty) in params {
+        function.new_param(&name, ty);
+    }
+    let declaration = function.to_string();
+
+    let tail = body_tail(&func.return_type);
+    generate_block(function.body(), options, &func.body, tail);
+    // crustal only prints a body that has something in it
+    if function.body().is_empty() {
+        function.body().return_none();
+    }
+
+    let template = if templates.is_empty() {
+        String::new()
+    } else {
+        let params = templates
+            .iter()
+            .map(|t| format!("typename {t}"))
+            .collect::<Vec<String>>();
+        format!("template <{}>\n", params.join(", "))
+    };
+    (
+        format!("{template}{declaration}"),
+        format!("{template}{function}"),
+    )
+}
+
+// Functions defined inside a block become static members of a struct
+// local to it, where they can call themselves and each other in any order.
+// They cannot refer to the variables around them, so nothing is lost, and a
+// static reference to each lets the rest of the block call it by name
+fn generate_local_functions(context: &mut CG::Block, options: &Options, funcs: &[&FunctionDef]) {
+    let name = format!("functions_{}", funcs[0].span.start);
+    let mut members = String::new();
+    for func in funcs {
+        let ret = get_crustal_type(func.return_type.as_ref().unwrap_or(&Type::None));
+        let mut function = CG::Function::new(&cpp_name(&func.name.value), ret);
+        // A local class cannot have templates, so function parameters are
+        // `std::function`s
+        for arg in &func.args {
+            let ty = get_crustal_type(&arg.associated_type);
+            function.new_param(&cpp_name(&arg.value.value), ty);
+        }
+        let tail = body_tail(&func.return_type);
+        generate_block(function.body(), options, &func.body, tail);
+        if function.body().is_empty() {
+            function.body().return_none();
+        }
+        for line in format!("static {function}").lines() {
+            writeln!(members, "    {line}").unwrap();
+        }
+    }
+    context.raw(format!("struct {name} {{\n{members}}}"));
+    for func in funcs {
+        let func = cpp_name(&func.name.value);
+        context.raw(format!("static constexpr auto &{func} = {name}::{func}"));
+    }
+}
+
+// A C++ lambda, copying the `val`s it uses and the boxes of the `var`s, next
+// to a reference to what each box holds. A `val` lambda that calls itself
+// goes through `bdl::fix`, which costs nothing over a plain call, while a
+// `var` one is a `std::function` that reaches itself through its box
+fn generate_lambda(options: &Options, lambda: &LambdaExpr) -> CG::Expr {
+    let captures = lambda
+        .captures
+        .iter()
+        .map(|c| {
+            if c.by_reference {
+                let name = cpp_name(&c.name);
+                format!("{}, &{name} = *{0}", shared_box(&name))
+            } else {
+                cpp_name(&c.name)
+            }
+        })
+        .collect::<Vec<String>>();
+    // Functions are taken as `auto`, as they are by local functions, and so
+    // are types left to the type variables of a generic function around the
+    // lambda, which C++ has no names for. The parameters are never written
+    // to, so nothing is copied to pass them
+    let mut params = lambda
+        .params
+        .iter()
+        .map(|p| match p.associated_type {
+            Type::FunctionType(..) => format!("auto {}", cpp_name(&p.value.value)),
+            ref t if !type_vars(t).is_empty() => {
+                format!("const auto &{}", cpp_name(&p.value.value))
+            }
+            _ => format!(
+                "const {} &{}",
+                get_string_type(&p.associated_type),
+                cpp_name(&p.value.value)
+            ),
+        })
+        .collect::<Vec<String>>();
+    // A lambda calling itself is handed itself under the name it calls
+    if let Some(name) = &lambda.recursive {
+        params.insert(0, format!("auto &&{}", cpp_name(name)));
+    }
+    let mut body = CG::Block::new();
+    let tail = if lambda.return_type == Type::None {
+        Tail::Discard
+    } else {
+        Tail::Return
+    };
+    generate_block(&mut body, options, &lambda.body, tail);
+    let body = body
+        .to_string()
+        .lines()
+        .map(|line| format!("    {line}\n"))
+        .collect::<String>();
+    let ret = if type_vars(&lambda.return_type).is_empty() {
+        get_crustal_type(&lambda.return_type).to_string()
+    } else {
+        "auto".to_string()
+    };
+    let code = format!(
+        "[{}]({}) -> {ret} {{\n{body}}}",
+        captures.join(", "),
+        params.join(", "),
+    );
+    match lambda.recursive {
+        Some(_) => CG::Expr::Raw(format!("bdl::fix({code})")),
+        None => CG::Expr::Raw(code),
     }
 }
 
 fn get_string_type(t: &Type) -> String {
     match t {
-        Type::Int => CG::Type::new_int32().to_string(),
+        Type::Int => "bdl::Int".to_string(),
+        Type::Float => CG::Type::new(CG::BaseType::Double).to_string(),
+        Type::Bool => CG::Type::new(CG::BaseType::Bool).to_string(),
+        Type::Range => "bdl::Range".to_string(),
         Type::String => CG::Type::new_std_string().to_string(),
+        Type::Var(v) => format!("T{v}"),
         Type::List(t) => CG::Type::new(CG::BaseType::TemplateClass(
             "std::vector".to_string(),
             vec![get_string_type(t)],
         ))
         .to_string(),
-        _ => todo!(),
+        Type::Tuple(ts) => CG::Type::new(CG::BaseType::TemplateClass(
+            "std::tuple".to_string(),
+            ts.iter().map(get_string_type).collect(),
+        ))
+        .to_string(),
+        Type::Named(name) => cpp_name(name),
+        Type::FunctionType(..) | Type::None | Type::Infer => get_crustal_type(t).to_string(),
     }
 }
 
 fn get_crustal_type(t: &Type) -> CG::Type {
     match t {
-        Type::Int => CG::Type::new_int32(),
+        Type::Int => CG::Type::new(CG::BaseType::Class("bdl::Int".to_string())),
+        Type::Float => CG::Type::new(CG::BaseType::Double),
+        Type::Bool => CG::Type::new(CG::BaseType::Bool),
+        Type::Range => CG::Type::new(CG::BaseType::Class("bdl::Range".to_string())),
         Type::String => CG::Type::new_std_string(),
+        Type::None => CG::Type::new_void(),
+        Type::Var(v) => CG::Type::new(CG::BaseType::Class(format!("T{v}"))),
         Type::List(t) => CG::Type::new(CG::BaseType::TemplateClass(
             "std::vector".to_string(),
             vec![get_string_type(t)],
         )),
-        _ => todo!(),
+        Type::Tuple(ts) => CG::Type::new(CG::BaseType::TemplateClass(
+            "std::tuple".to_string(),
+            ts.iter().map(get_string_type).collect(),
+        )),
+        Type::Named(name) => CG::Type::new(CG::BaseType::Class(cpp_name(name))),
+        // Where a function has to be stored under a single type, such as
+        // when it is returned, it goes behind a `std::function`
+        Type::FunctionType(args, ret) => {
+            let args = args.iter().map(get_string_type).collect::<Vec<String>>();
+            let ret = get_crustal_type(ret.as_ref().as_ref().unwrap_or(&Type::None));
+            CG::Type::new(CG::BaseType::Class(format!(
+                "std::function<{ret}({})>",
+                args.join(", ")
+            )))
+        }
+        // The type checker reports every type it cannot decide, and nothing
+        // is generated for a program with errors
+        Type::Infer => unreachable!("an undecided type reached code generation"),
     }
 }
 
-fn generate_assignment(context: &mut CG::Block, assign: &AssignmentExpr) {
-    let var = context.new_variable(
-        &assign.target.value.value,
-        get_crustal_type(&assign.target.associated_type),
-    );
+fn generate_assignment(context: &mut CG::Block, options: &Options, assign: &AssignmentExpr) {
+    let name = &cpp_name(&assign.target.value.value);
+    // A `val` keeps the exact type of the function it is given, which C++
+    // only lets it spell as `auto`
+    if assign.const_var && matches!(assign.target.associated_type, Type::FunctionType(..)) {
+        let rhs = process_expression(context, options, &assign.value).unwrap();
+        context.raw(format!("const auto {name} = {rhs}"));
+        return;
+    }
+    let mut ty = get_crustal_type(&assign.target.associated_type);
+    if assign.const_var {
+        // The typechecker has rejected every write to a `val`, so the C++
+        // compiler is free to treat it as a constant
+        ty.set_value_const();
+        let rhs = process_expression(context, options, &assign.value).unwrap();
+        context.variable(CG::Variable::with_value(name, ty, rhs));
+        return;
+    }
+    if assign.shared {
+        // Declared before its value, which may be a lambda that uses it
+        declare_shared(context, name, &ty);
+        let rhs = process_expression(context, options, &assign.value).unwrap();
+        context.assign(CG::Expr::Raw(name.clone()), rhs);
+        return;
+    }
+    let var = context.new_variable(name, ty);
     let expr = var.to_expr();
-    let rhs = process_expression(context, &assign.value).unwrap();
+    let rhs = process_expression(context, options, &assign.value).unwrap();
     context.assign(expr, rhs);
 }
 
-fn generate_reassignment(context: &mut CG::Block, assign: &ReassignmentExpr) {
-    let var_expr = CG::Expr::new_var(&assign.target.value, CG::Type::new_void());
-    let rhs = process_expression(context, &assign.value).unwrap();
+// A `var` that lambdas share lives on the heap, in a box each of them holds
+// on to, so that it lasts as long as the last of them even when they outlive
+// the block declaring it. `name` refers to what is in the box
+fn declare_shared(context: &mut CG::Block, name: &str, ty: &CG::Type) {
+    let shared = shared_box(name);
+    context.raw(format!("auto {shared} = std::make_shared<{ty}>()"));
+    context.raw(format!("{ty} &{name} = *{shared}"));
+}
+
+fn shared_box(name: &str) -> String {
+    format!("box_{name}")
+}
+
+// The targets already agree with the value, so a structured binding can
+// leave their types to C++
+fn generate_destructure(context: &mut CG::Block, options: &Options, destructure: &DestructureExpr) {
+    let value = process_expression(context, options, &destructure.value).unwrap();
+    if destructure.shared {
+        let tuple = format!("value_{}", destructure.span.start);
+        context.raw(format!("auto {tuple} = {value}"));
+        for (i, target) in destructure.targets.iter().enumerate() {
+            let name = cpp_name(&target.value.value);
+            declare_shared(context, &name, &get_crustal_type(&target.associated_type));
+            context.raw(format!("{name} = std::get<{i}>(std::move({tuple}))"));
+        }
+        return;
+    }
+    let names = destructure
+        .targets
+        .iter()
+        .map(|t| cpp_name(&t.value.value))
+        .collect::<Vec<String>>();
+    let qualifier = if destructure.const_var { "const " } else { "" };
+    context.raw(format!("{qualifier}auto [{}] = {value}", names.join(", ")));
+}
+
+// `x += e` is emitted as `x = x + e`, so that it promotes and checks for
+// overflow the way `+` does. An element or a field is found once, through a
+// reference, so that the index is only evaluated once. Its name has no
+// `bdl_` prefix, so it cannot meet a name from the program
+fn generate_reassignment(context: &mut CG::Block, options: &Options, assign: &ReassignmentExpr) {
+    let mut var_expr = process_expression(context, options, &assign.target).unwrap();
+    let rhs = match assign.op.strip_suffix('=') {
+        Some(op) if !op.is_empty() => {
+            if !matches!(*assign.target, Expr::Identifier(_)) {
+                let name = format!("target_{}", assign.span.start);
+                context.raw(format!("auto &&{name} = {var_expr}"));
+                var_expr = CG::Expr::Raw(name);
+            }
+            let value = process_expression(context, options, &assign.value).unwrap();
+            apply_binop(options, var_expr.clone(), op, value, assign.span)
+        }
+        _ => process_expression(context, options, &assign.value).unwrap(),
+    };
     context.assign(var_expr, rhs);
 }
 
-fn generate_method_call(context: &mut CG::Block, call: &MethodCallExpr) {
+fn generate_method_call(
+    context: &mut CG::Block,
+    options: &Options,
+    call: &MethodCallExpr,
+) -> CG::Expr {
     let obj_name = &call.method_name.value;
-    let args_expr = call
+    let mut args_expr = call
         .args
         .iter()
-        .map(|arg| process_expression(context, arg).unwrap())
-        .collect();
-    context.fn_call(obj_name, args_expr);
+        .map(|arg| process_expression(context, options, arg).unwrap())
+        .collect::<Vec<CG::Expr>>();
+    if !call.builtin {
+        return CG::Expr::fn_call(&cpp_name(obj_name), args_expr);
+    }
+
+    // Built-ins that fail on an empty list say where they were called, and
+    // `sum` adds the way `+` does in the current mode
+    let span = &call.span;
+    let location = format!("{}:{}:{}", options.file, span.line, span.col);
+    match obj_name.as_str() {
+        "reduce" | "min" | "max" | "pop" => args_expr.push(CG::Expr::Raw(format!("{location:?}"))),
+        "sum" if options.arith == ArithMode::Checked => args_expr.push(CG::Expr::Raw(format!(
+            "[](const auto &a, const auto &b) {{ return bdl::checked_add(a, b, {location:?}); }}"
+        ))),
+        "sum" => args_expr.push(CG::Expr::Raw("std::plus<>()".to_string())),
+        _ => {}
+    }
+    CG::Expr::fn_call(&format!("bdl::list::{obj_name}"), args_expr)
 }
 
-fn generate_print(context: &mut CG::Block, print: &PrintExpr) {
-    let expr = process_expression(context, &print.arg).unwrap();
-    // TODO: Print string
+fn generate_print(context: &mut CG::Block, options: &Options, print: &PrintExpr) {
+    let expr = process_expression(context, options, &print.arg).unwrap();
+    context.raw_expr(CG::Expr::fn_call("bdl::print", vec![expr]));
 }
 
-fn generate_if(context: &mut CG::Block, if_expr: &IfExpr) {
-    let cond = process_expression(context, &if_expr.condition).unwrap();
+fn generate_if(context: &mut CG::Block, options: &Options, if_expr: &IfExpr) {
+    let cond = process_expression(context, options, &if_expr.condition).unwrap();
     let if_else_expr = context.new_ifelse(&cond);
 
-    let then_block = if_else_expr.then_branch();
-    for expr in if_expr.then_block.iter() {
-        process_expression(then_block, expr).unwrap();
-    }
-    let else_block = if_else_expr.other_branch();
+    generate_block(
+        if_else_expr.then_branch(),
+        options,
+        &if_expr.then_block,
+        Tail::Discard,
+    );
     if let Some(else_vec) = &if_expr.else_block {
-        for expr in else_vec.iter() {
-            process_expression(else_block, expr).unwrap();
+        generate_block(
+            if_else_expr.other_branch(),
+            options,
+            else_vec,
+            Tail::Discard,
+        );
+    }
+}
+
+// An `if` whose value is used, with branches that store it
+fn generate_if_value(options: &Options, if_expr: &IfExpr) -> CG::Expr {
+    let name = format!("value_{}", if_expr.span.start);
+    let mut body = CG::Block::new();
+    generate_if_tail(&mut body, options, if_expr, Tail::Store(&name));
+    statement_expression(&body, &name, &if_expr.ty)
+}
+
+// Tests the arms in order, with the patterns as nested `if`s. An arm either
+// returns its value, or jumps past the arms after it
+fn generate_match(context: &mut CG::Block, options: &Options, m: &MatchExpr, tail: Tail) {
+    // Named after where the match starts, so that nested matches differ
+    let name = format!("match_{}", m.span.start);
+    let end = format!("{name}_end");
+    let scrutinee = process_expression(context, options, &m.scrutinee).unwrap();
+    context.raw(format!("const auto {name} = {scrutinee}"));
+    for arm in &m.arms {
+        let mut on_match = |block: &mut CG::Block| {
+            let block = match &arm.guard {
+                Some(guard) => {
+                    let cond = process_expression(block, options, guard).unwrap();
+                    block.new_ifelse(&cond).then_branch()
+                }
+                None => block,
+            };
+            generate_block(block, options, &arm.body, tail);
+            if !matches!(tail, Tail::Return) {
+                block.goto(&end);
+            }
+        };
+        // An arm that matches anything still needs a scope of its own
+        let block = match arm.pattern {
+            Pattern::Wildcard(_) | Pattern::Binding(_) => context
+                .new_ifelse(&CG::Expr::Raw("true".to_string()))
+                .then_branch(),
+            _ => &mut *context,
+        };
+        generate_pattern(block, options, &arm.pattern, &name, &mut on_match);
+    }
+    if let Tail::Return = tail {
+        // The type checker made sure that some arm matches
+        context.raw_str("__builtin_unreachable()");
+    } else {
+        context.raw(format!("{end}:"));
+    }
+}
+
+// A match whose value is used, with arms that store it
+fn generate_match_value(options: &Options, m: &MatchExpr) -> CG::Expr {
+    let name = format!("value_{}", m.span.start);
+    let mut body = CG::Block::new();
+    generate_match(&mut body, options, m, Tail::Store(&name));
+    statement_expression(&body, &name, &m.ty)
+}
+
+// `({ std::optional<ty> name; body *std::move(name); })`, a GNU statement
+// expression for a value that takes statements to compute. Unlike a lambda,
+// it lets a `return`, `break` or `continue` in `body` leave the function or
+// loop around it, and unlike a variable declared before the statement, it is
+// evaluated where the value is needed
+fn statement_expression(body: &CG::Block, name: &str, ty: &Type) -> CG::Expr {
+    let body = body
+        .to_string()
+        .lines()
+        .map(|line| format!("    {line}\n"))
+        .collect::<String>();
+    let ty = get_crustal_type(ty);
+    CG::Expr::Raw(format!(
+        "({{\n    std::optional<{ty}> {name};\n{body}    *std::move({name});\n}})"
+    ))
+}
+
+// Emits the tests of `pattern` against the C++ expression `value` and binds
+// its names, calling `on_match` for the block where it has matched
+fn generate_pattern(
+    context: &mut CG::Block,
+    options: &Options,
+    pattern: &Pattern,
+    value: &str,
+    on_match: &mut dyn FnMut(&mut CG::Block),
+) {
+    match pattern {
+        Pattern::Wildcard(_) => on_match(context),
+        Pattern::Binding(binding) => {
+            context.raw(format!(
+                "const auto &{} = {value}",
+                cpp_name(&binding.value.value)
+            ));
+            on_match(context);
+        }
+        Pattern::Literal(literal) => {
+            let literal = process_expression(context, options, literal).unwrap();
+            let cond = CG::Expr::binop(CG::Expr::Raw(value.to_string()), "==", literal);
+            on_match(context.new_ifelse(&cond).then_branch());
+        }
+        Pattern::Constructor(c) => {
+            let variant = format!("{}::{}", cpp_name(&c.enum_name), cpp_name(&c.name.value));
+            let cond = CG::Expr::Raw(format!("std::holds_alternative<{variant}>(*{value}.value)"));
+            let block = context.new_ifelse(&cond).then_branch();
+            if c.args.is_empty() {
+                on_match(block);
+                return;
+            }
+            // The fields are named after the value they belong to
+            let fields = (0..c.args.len())
+                .map(|i| format!("{value}_{i}"))
+                .collect::<Vec<String>>();
+            block.raw(format!(
+                "const auto &[{}] = std::get<{variant}>(*{value}.value)",
+                fields.join(", ")
+            ));
+            generate_patterns(block, options, &c.args, &fields, on_match);
         }
     }
 }
 
+fn generate_patterns(
+    context: &mut CG::Block,
+    options: &Options,
+    patterns: &[Pattern],
+    values: &[String],
+    on_match: &mut dyn FnMut(&mut CG::Block),
+) {
+    match patterns.split_first() {
+        Some((first, rest)) => {
+            generate_pattern(context, options, first, &values[0], &mut |block| {
+                generate_patterns(block, options, rest, &values[1..], on_match)
+            })
+        }
+        None => on_match(context),
+    }
+}
+
 fn generate_variable_name() -> String {
     use rand::Rng;
     let mut rng = rand::thread_rng();
@@ -157,55 +1083,211 @@ fn generate_variable_name() -> String {
     name
 }
 
-fn generate_rep(context: &mut CG::Block, rep: &RepExpr) {
-    let cond = process_expression(context, &rep.num_iterations).unwrap();
-    // assume integer for now
-    let var_name = generate_variable_name();
-    let var_expr = context
-        .new_variable(&var_name, CG::Type::new_int32())
-        .to_expr();
+fn generate_rep(context: &mut CG::Block, options: &Options, rep: &RepExpr) {
+    // The count is evaluated once, before the first iteration
+    let count = process_expression(context, options, &rep.num_iterations).unwrap();
+    let count_name = generate_variable_name();
+    let mut count_type = get_crustal_type(&Type::Int);
+    count_type.set_value_const();
+    context.variable(CG::Variable::with_value(&count_name, count_type, count));
+
+    // Counted at the start of the body, so that `continue` still counts the
+    // iteration
+    let counter = generate_variable_name();
+    context.raw(format!("int64_t {counter} = 0"));
+    let body = context
+        .new_while_loop(&CG::Expr::Raw(format!("{counter} < {count_name}")))
+        .body();
+    body.raw(format!("{counter}++"));
+    generate_loop_body(body, options, &rep.body, rep.label.is_some(), rep.span);
+    generate_break_label(context, rep.label.is_some(), rep.span);
+}
+
+fn generate_while(context: &mut CG::Block, options: &Options, w: &WhileExpr) {
+    let cond = process_expression(context, options, &w.condition).unwrap();
+    let labelled = w.label.is_some();
+    let body = context.new_while_loop(&cond).body();
+    generate_loop_body(body, options, &w.body, labelled, w.span);
+    generate_break_label(context, labelled, w.span);
+}
+
+// Lists and ranges are both walked with a range-based `for` over
+// `bdl::each`, which reads a list by index so that the body can push to it.
+// `enumerate` in a loop pairs up the indices without making a new list
+fn generate_for(context: &mut CG::Block, options: &Options, f: &ForExpr) {
+    let iterable = match f.iterable.as_ref() {
+        Expr::MethodCallExpr(call) if call.builtin && call.method_name.value == "enumerate" => {
+            let list = process_expression(context, options, &call.args[0]).unwrap();
+            format!("bdl::each_enumerated({list})")
+        }
+        iterable => {
+            let iterable = process_expression(context, options, iterable).unwrap();
+            format!("bdl::each({iterable})")
+        }
+    };
+    let names = f
+        .targets
+        .iter()
+        .map(|t| cpp_name(&t.value.value))
+        .collect::<Vec<String>>();
+    let target = match names.as_slice() {
+        [name] => name.clone(),
+        names => format!("[{}]", names.join(", ")),
+    };
+
+    // crustal's `ForLoop` leaves out the body of a loop that has one, so the
+    // loop is written out here
+    let labelled = f.label.is_some();
+    let mut body = CG::Block::new();
+    generate_loop_body(&mut body, options, &f.body, labelled, f.span);
+    let body = body
+        .to_string()
+        .lines()
+        .map(|line| format!("    {line}\n"))
+        .collect::<String>();
+    context.raw(format!(
+        "for (const auto &{target} : {iterable}) {{\n{body}}}"
+    ));
+    generate_break_label(context, labelled, f.span);
+}
+
+// A range without a step counts up by one
+fn generate_range(context: &mut CG::Block, options: &Options, range: &RangeExpr) -> CG::Expr {
+    let start = process_expression(context, options, &range.start).unwrap();
+    let stop = process_expression(context, options, &range.stop).unwrap();
+    let step = match &range.step {
+        Some(step) => process_expression(context, options, step).unwrap(),
+        None => CG::Expr::Raw("bdl::Int(1)".to_string()),
+    };
+    let span = &range.span;
+    let location = format!("{}:{}:{}", options.file, span.line, span.col);
+    CG::Expr::fn_call(
+        "bdl::range",
+        vec![start, stop, step, CG::Expr::Raw(format!("{location:?}"))],
+    )
+}
 
-    // This is very stupid
-    let var_expr_copy = var_expr.clone();
-    let var_expr_copy_2 = var_expr.clone();
-    let var_expr_copy_3 = var_expr.clone();
+// C++ has no labelled `break` or `continue`, so a labelled loop is left with
+// a `goto`. Its labels are named after where it starts, as those of a match
+fn loop_label(start: usize, jump: &str) -> String {
+    format!("loop_{start}_{jump}")
+}
 
-    context.assign(var_expr, CG::Expr::new_num(0));
-    let cond_expr = CG::Expr::binop(var_expr_copy, "<", cond);
+// The body of a labelled loop gets a scope of its own, so that jumping to
+// its `continue` label at the end skips no initializations
+fn generate_loop_body(
+    context: &mut CG::Block,
+    options: &Options,
+    body: &[Expr],
+    labelled: bool,
+    span: Span,
+) {
+    if !labelled {
+        generate_block(context, options, body, Tail::Discard);
+        return;
+    }
+    let block = context
+        .new_ifelse(&CG::Expr::Raw("true".to_string()))
+        .then_branch();
+    generate_block(block, options, body, Tail::Discard);
+    context.raw(format!("{}:", loop_label(span.start, "continue")));
+}
 
-    let while_loop = context.new_while_loop(&cond_expr);
-    let while_loop_body = while_loop.body();
-    for expr in rep.body.iter() {
-        process_expression(while_loop_body, expr);
+fn generate_break_label(context: &mut CG::Block, labelled: bool, span: Span) {
+    if labelled {
+        context.raw(format!("{}:", loop_label(span.start, "break")));
+    }
+}
+
+fn generate_jump(context: &mut CG::Block, label: &Option<Identifier>, target: usize, jump: &str) {
+    match (label, jump) {
+        (Some(_), _) => context.goto(&loop_label(target, jump)),
+        (None, "break") => context.break_stmt(),
+        (None, _) => context.continue_stmt(),
+    };
+}
+
+// Literals are wrapped so that they are `bdl::Int`s wherever C++ deduces a
+// type from them. Those past 64 bits are spelled out in decimal
+fn generate_integer(options: &Options, int: &IntegerLiteral) -> CG::Expr {
+    // Out of range literals are left for the C++ compiler to reject when
+    // ints are fixed-width
+    if i64::try_from(int.value).is_ok() || options.arith != ArithMode::BigInt {
+        CG::Expr::Raw(format!("bdl::Int({})", int.value))
+    } else {
+        CG::Expr::Raw(format!("bdl::Int(\"{}\")", int.value))
+    }
+}
+
+// Rust writes the shortest digits that read back as the same double, which
+// C++ reads back exactly too
+fn generate_float(float: &FloatLiteral) -> CG::Expr {
+    if float.value.is_infinite() {
+        CG::Expr::Raw("HUGE_VAL".to_string())
+    } else {
+        CG::Expr::Raw(format!("{:?}", float.value))
     }
-    while_loop_body.assign(
-        var_expr_copy_2,
-        CG::Expr::binop(var_expr_copy_3, "+", CG::Expr::new_num(1)),
-    );
 }
 
-fn generate_binop(context: &mut CG::Block, binop: &BinOpExpr) -> CG::Expr {
-    let left = process_expression(context, &binop.left).unwrap();
-    let right = process_expression(context, &binop.right).unwrap();
-    let op = binop.op.as_str();
+fn generate_binop(context: &mut CG::Block, options: &Options, binop: &BinOpExpr) -> CG::Expr {
+    let left = process_expression(context, options, &binop.left).unwrap();
+    let right = process_expression(context, options, &binop.right).unwrap();
+    apply_binop(options, left, &binop.op, right, binop.span)
+}
+
+// `left op right`, through the checked helpers where arithmetic is checked
+fn apply_binop(
+    options: &Options,
+    left: CG::Expr,
+    op: &str,
+    right: CG::Expr,
+    span: Span,
+) -> CG::Expr {
+    let helper = match op {
+        "+" => "add",
+        "-" => "sub",
+        "*" => "mul",
+        _ => "",
+    };
+    if options.arith == ArithMode::Checked && !helper.is_empty() {
+        // The helpers fall back to the plain operator for anything but ints
+        let location = format!("{}:{}:{}", options.file, span.line, span.col);
+        return CG::Expr::fn_call(
+            &format!("bdl::checked_{helper}"),
+            vec![left, right, CG::Expr::Raw(format!("{location:?}"))],
+        );
+    }
     CG::Expr::binop(left, op, right)
 }
 
-fn generate_unop(context: &mut CG::Block, unop: &UnOpExpr) -> CG::Expr {
-    let expr = process_expression(context, &unop.arg).unwrap();
+fn generate_unop(context: &mut CG::Block, options: &Options, unop: &UnOpExpr) -> CG::Expr {
+    let expr = process_expression(context, options, &unop.arg).unwrap();
     let op = unop.op.as_str();
     CG::Expr::uop(op, expr)
 }
 
-fn generate_list_expr(context: &mut CG::Block, list: &ListExpr) -> CG::Expr {
+// A list is spelled with its type, as C++ can neither deduce the type of an
+// empty one nor tell a list holding one list from a copy of that list.
+// Types left to the type variables of a generic function have no name in
+// C++, so those are deduced from the elements, or for an empty list from
+// the variable it initialises
+fn generate_list_expr(context: &mut CG::Block, options: &Options, list: &ListExpr) -> CG::Expr {
+    let ty = if type_vars(&list.ty).is_empty() {
+        get_string_type(&list.ty)
+    } else if list.elems.is_empty() {
+        return CG::Expr::Raw("{}".to_string());
+    } else {
+        "std::vector".to_string()
+    };
+
     let cg_elems = list
         .elems
         .iter()
-        .map(|expr| process_expression(context, expr).unwrap())
+        .map(|expr| process_expression(context, options, expr).unwrap())
         .collect::<Vec<CG::Expr>>();
 
     CG::Expr::Raw(format!(
-        "vector{{ {} }}",
+        "{ty}{{ {} }}",
         cg_elems
             .iter()
             .map(|e| e.to_string())
@@ -214,9 +1296,44 @@ fn generate_list_expr(context: &mut CG::Block, list: &ListExpr) -> CG::Expr {
     ))
 }
 
-fn generate_identifier(context: &mut CG::Block, id: &Identifier) -> CG::Expr {
+fn generate_tuple_expr(context: &mut CG::Block, options: &Options, tuple: &TupleExpr) -> CG::Expr {
+    let elems = tuple
+        .elems
+        .iter()
+        .map(|expr| process_expression(context, options, expr).unwrap())
+        .collect();
+    CG::Expr::fn_call("std::make_tuple", elems)
+}
+
+fn generate_field_access(
+    context: &mut CG::Block,
+    options: &Options,
+    access: &FieldAccessExpr,
+) -> CG::Expr {
+    let object = process_expression(context, options, &access.object).unwrap();
+    // The fields of a tuple are numbered
+    if access.field.parse::<usize>().is_ok() {
+        CG::Expr::Raw(format!("std::get<{}>({object})", access.field))
+    } else {
+        CG::Expr::Raw(format!("{object}.{}", cpp_name(&access.field)))
+    }
+}
+
+// Indexing is bounds checked, reporting where it happened
+fn generate_index(context: &mut CG::Block, options: &Options, index: &IndexExpr) -> CG::Expr {
+    let object = process_expression(context, options, &index.object).unwrap();
+    let position = process_expression(context, options, &index.index).unwrap();
+    let span = &index.span;
+    let location = format!("{}:{}:{}", options.file, span.line, span.col);
+    CG::Expr::fn_call(
+        "bdl::list::at",
+        vec![object, position, CG::Expr::Raw(format!("{location:?}"))],
+    )
+}
+
+fn generate_identifier(id: &Identifier) -> CG::Expr {
     CG::Expr::Variable {
-        name: id.value.clone(),
+        name: cpp_name(&id.value),
         ty: CG::Type::new_int32(),
     }
 }
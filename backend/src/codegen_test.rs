@@ -0,0 +1,1147 @@
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{generate, ArithMode, Options};
+    use crate::runtime::{INT, PRELUDE};
+    use bdl_frontend::parser::parse_program;
+    use bdl_frontend::typeck::check_program;
+    use std::process::{Command, Output};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn generate_source(input: &str) -> String {
+        generate_source_with(input, ArithMode::BigInt)
+    }
+
+    fn generate_source_with(input: &str, arith: ArithMode) -> String {
+        let mut program = parse_program(input).unwrap().Program().unwrap();
+        check_program(&mut program).unwrap();
+        let options = Options {
+            arith,
+            file: "test.bdl".to_string(),
+        };
+        generate(&program, &options)
+    }
+
+    // Compiles `code` with g++ and runs it, returning what it printed, or
+    // `None` when g++ is not installed
+    fn run(code: &str) -> Option<String> {
+        let output = execute(code)?;
+        assert!(output.status.success(), "{code}");
+        Some(String::from_utf8(output.stdout).unwrap())
+    }
+
+    // As `run`, but leaves checking how the program exited to the caller
+    fn execute(code: &str) -> Option<Output> {
+        Command::new("g++").arg("--version").output().ok()?;
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!("bdl-codegen-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(COUNT.fetch_add(1, Ordering::Relaxed).to_string());
+        std::fs::write(path.with_extension("cpp"), code).unwrap();
+        let output = Command::new("g++")
+            .args(["-std=c++17", "-o"])
+            .arg(&path)
+            .arg(path.with_extension("cpp"))
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{code}\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Some(Command::new(&path).output().unwrap())
+    }
+
+    fn assert_compiles(code: &str) {
+        run(code);
+    }
+
+    #[test]
+    fn test_functions_are_hoisted() {
+        let code = generate_source(
+            r#"val x = twice(2)
+def twice(n: int): int {
+return n * 2
+}
+"#,
+        );
+        let main = code.find("main(").unwrap();
+        assert!(code.find("bdl::Int bdl_twice(bdl::Int bdl_n);").unwrap() < main);
+        assert!(code.contains("return (bdl_n * bdl::Int(2));"));
+        assert!(code[main..].contains("bdl_x = bdl_twice(bdl::Int(2));"));
+        assert_compiles(&code);
+    }
+
+    #[test]
+    fn test_mutual_recursion() {
+        let code = generate_source(
+            r#"def is_even(n) {
+if n == 0 {
+return 1 == 1
+}
+is_odd(n - 1)
+}
+def is_odd(n) {
+if n == 0 {
+return 1 == 0
+}
+is_even(n - 1)
+}
+val b = is_odd(7)
+"#,
+        );
+        // Both are declared before either is defined
+        let declaration = code.find("bool bdl_is_odd(bdl::Int bdl_n);").unwrap();
+        assert!(declaration < code.find("bool bdl_is_even(bdl::Int bdl_n) {").unwrap());
+        assert_compiles(&code);
+    }
+
+    #[test]
+    fn test_generic_functions_are_templates() {
+        let code = generate_source(
+            r#"def add(a, b) {
+a + b
+}
+def apply(f, x) {
+f(x)
+}
+def inc(n: int) {
+n + 1
+}
+val n = add(1, 2)
+val s = add("a", "b")
+val m = apply(inc, n)
+"#,
+        );
+        assert!(code.contains("template <typename T"));
+        assert!(code.contains(" bdl_add(T"));
+        assert!(code.contains("template <typename F0, typename "));
+        assert_compiles(&code);
+    }
+
+    #[test]
+    fn test_tail_values_are_returned() {
+        let code = generate_source(
+            r#"def sign(n: int) {
+def neg(k: int) {
+0 - k
+}
+if n < 0 {
+neg(1)
+} else {
+1
+}
+}
+def log(s: string) {
+print(s)
+}
+sign(2)
+"#,
+        );
+        assert!(code.contains("    static bdl::Int bdl_neg(bdl::Int bdl_k) {"));
+        assert!(code.contains("return bdl_neg(bdl::Int(1));"));
+        assert!(code.contains("return bdl::Int(1);"));
+        assert!(code.contains("void bdl_log(std::string bdl_s) {"));
+        assert_compiles(&code);
+    }
+
+    #[test]
+    fn test_nested_functions() {
+        let source = r#"def outer(n: int): int {
+def fact(k: int): int {
+if k == 0 {
+1
+} else {
+k * fact(k - 1)
+}
+}
+def a(k: int): int {
+b(k) + 1
+}
+def b(k: int): int {
+k * 10
+}
+val step = (k: int) => a(k) + fact(k)
+if n > 0 {
+def c(k: int): int {
+fact(k) + 100
+}
+print(c(n))
+}
+map([1, 2, n], a)[2] + step(n)
+}
+def pick(x) {
+def same(y) {
+y
+}
+same(x)
+}
+print(outer(3))
+print((pick("kept"), pick(2)))
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("static constexpr auto &bdl_fact = functions_"));
+        let expected = "106\n68\n('kept', 2)\n";
+        assert_eq!(run(&code).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_if_values() {
+        let source = r#"val c = 2 > 1
+val x = if c {
+1
+} else {
+2
+}
+print([if c {
+x + 1
+} else {
+0
+}, x])
+print(if !c {
+"yes"
+} else {
+"no"
+})
+if c {
+print(x)
+1
+} else {
+2
+}
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("std::optional<bdl::Int> value_"));
+        assert_eq!(run(&code).unwrap(), "[2, 1]\nno\n1\n");
+    }
+
+    #[test]
+    fn test_control_flow_in_if_values() {
+        let source = r#"def f(c: bool): int {
+val x = if c {
+return 5
+} else {
+3
+}
+x + 100
+}
+print((f(true), f(false)))
+var total = 0
+for n in 0..10 {
+total += if n == 7 {
+break
+} else {
+if n == 2 {
+continue
+} else {
+n
+}
+}
+}
+print(total)
+var i = 0
+while if i < 3 {
+true
+} else {
+false
+} {
+i += 1
+}
+print(i)
+"#;
+        let code = generate_source(source);
+        assert_eq!(run(&code).unwrap(), "(5, 103)\n19\n3\n");
+    }
+
+    #[test]
+    fn test_translation_unit() {
+        let code = generate_source("print(1 + 2)\nprint(\"done\")\n");
+        assert!(code.starts_with("#include <bits/stdc++.h>\n"));
+        assert!(code.contains("namespace bdl {"));
+        assert!(code.contains("int main(void) {"));
+        assert!(code.trim_end().ends_with("return 0;\n}"));
+        if let Some(output) = run(&code) {
+            assert_eq!(output, "3\ndone\n");
+        }
+    }
+
+    #[test]
+    fn test_print_formatting() {
+        let code = generate_source(
+            r#"val xs = [1, 2, 3]
+print(xs)
+print([[1], [2, 3]])
+print(["a", "it's"])
+print("plain")
+print(1 < 2)
+print([1 == 2])
+"#,
+        );
+        if let Some(output) = run(&code) {
+            assert_eq!(
+                output,
+                "[1, 2, 3]\n[[1], [2, 3]]\n['a', \"it's\"]\nplain\nTrue\n[False]\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_printing_every_type() {
+        let source = r#"struct P { x: int, name: string }
+enum E { A, B(v: float), C(p: P, es: list<E>) }
+val r = 0..6 step 2
+print([(1, 2.5, true, "s", [1], (2, "t"), P(3, "p"), A, B(1.5), r)])
+print([[P(1, "a")], []])
+print([[[1]]])
+print(([A, C(P(2, "b"), [B(0.1), A])], (r, [r, 3..0 step -1])))
+print([[[1, 2], [3]], [[]]])
+print(("x", ("y", [false, true]), [("z", 100000000000000000000 * 10)]))
+print((1, []))
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("std::vector<std::vector<bdl_P>>{ std::vector<bdl_P>{ bdl_P("));
+        assert!(code.contains("std::vector<bdl_P>{  }"));
+        let expected = "[(1, 2.5, True, 's', [1], (2, 't'), P(x=3, name='p'), A, B(v=1.5), \
+                        range(0, 6, 2))]\n\
+                        [[P(x=1, name='a')], []]\n\
+                        [[[1]]]\n\
+                        ([A, C(p=P(x=2, name='b'), es=[B(v=0.1), A])], \
+                        (range(0, 6, 2), [range(0, 6, 2), range(3, 0, -1)]))\n\
+                        [[[1, 2], [3]], [[]]]\n\
+                        ('x', ('y', [False, True]), [('z', 1000000000000000000000)])\n\
+                        (1, [])\n";
+        assert_eq!(run(&code).unwrap(), expected);
+    }
+
+    // Values BDL cannot write literally yet, printed straight from C++
+    #[test]
+    fn test_runtime_formatting() {
+        let code = format!(
+            r#"#include <bits/stdc++.h>
+{INT}
+{PRELUDE}
+int main() {{
+    for (double d : {{0.0, -0.0, 1.0, 0.1, 1e16, 1e15, 1e-5, 0.0001, 1.0 / 3}}) {{
+        bdl::print(d);
+    }}
+    bdl::print(std::make_tuple(1, std::string("x"), 2.5, false));
+    bdl::print(std::make_tuple(1));
+    bdl::print(std::vector<bool>{{true, false}});
+}}
+"#
+        );
+        if let Some(output) = run(&code) {
+            assert_eq!(
+                output,
+                "0.0\n-0.0\n1.0\n0.1\n1e+16\n1000000000000000.0\n1e-05\n0.0001\n\
+                 0.3333333333333333\n(1, 'x', 2.5, False)\n(1,)\n[True, False]\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_ints_grow_past_64_bits() {
+        let code = generate_source(
+            r#"def fact(n) {
+if n < 2 {
+return 1
+}
+n * fact(n - 1)
+}
+print(fact(25))
+print(9223372036854775807 + 1)
+print(100000000000000000000000 / 1000 - 1)
+val big = fact(30) / fact(28)
+print([big, 0 - big])
+"#,
+        );
+        assert!(code.contains("bdl::Int(\"100000000000000000000000\")"));
+        if let Some(output) = run(&code) {
+            assert_eq!(
+                output,
+                "15511210043330985984000000\n9223372036854775808\n99999999999999999999\n[870, -870]\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let source = r#"val a = 9223372036854775807
+val s = "x" + "y"
+print(s)
+print(a - 1)
+print(1 +   a)
+"#;
+        let code = generate_source_with(source, ArithMode::Checked);
+        assert!(code.contains(r#"bdl::checked_sub(bdl_a, bdl::Int(1), "test.bdl:4:7")"#));
+        assert!(!code.contains("class Int"));
+        if let Some(output) = execute(&code) {
+            assert!(!output.status.success());
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                "xy\n9223372036854775806\n"
+            );
+            assert_eq!(
+                String::from_utf8(output.stderr).unwrap(),
+                "test.bdl:5:7: error: integer overflow in `+`\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_release_arithmetic() {
+        let code = generate_source_with(
+            "val a = 9223372036854775807\nprint(a * 2 + 3 - 1)\n",
+            ArithMode::Release,
+        );
+        assert!(code.contains("(((bdl_a * bdl::Int(2)) + bdl::Int(3)) - bdl::Int(1))"));
+        assert!(!code.contains("bdl::checked_"));
+        assert_compiles(&code);
+    }
+
+    #[test]
+    fn test_floats() {
+        let source = r#"val n = 3
+val x = 0.1
+print(x)
+print(x + 0.2)
+print(n * 0.5)
+print(1.5 - n)
+print(n / 2)
+print(7 / 2.0)
+print(n < 3.5)
+print(n == 3.0)
+print([2.0, 100000000000000000.0, 0.1 * 3.0])
+print(0.0 - 0.000012)
+"#;
+        let expected = "0.1\n0.30000000000000004\n1.5\n-1.5\n1\n3.5\nTrue\nTrue\n\
+                        [2.0, 1e+17, 0.30000000000000004]\n-1.2e-05\n";
+        let code = generate_source(source);
+        assert!(code.contains("bdl_x = 0.1;"));
+        if let Some(output) = run(&code) {
+            assert_eq!(output, expected);
+        }
+        for arith in [ArithMode::Checked, ArithMode::Release] {
+            if let Some(output) = run(&generate_source_with(source, arith)) {
+                assert_eq!(output, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_booleans() {
+        let code = generate_source(
+            r#"val ok: bool = true
+val n = 3
+val both = ok && !(n > 5)
+print(both)
+print([ok, !ok, n == 3 || false])
+if !both {
+print("no")
+} else {
+print("yes")
+}
+"#,
+        );
+        assert!(code.contains("bdl_both = (bdl_ok && !((bdl_n > bdl::Int(5))));"));
+        if let Some(output) = run(&code) {
+            assert_eq!(output, "True\n[True, False, True]\nyes\n");
+        }
+    }
+
+    #[test]
+    fn test_tuples() {
+        let code = generate_source(
+            r#"def divmod(a: int, b: int): tuple<int, int> {
+(a / b, a - a / b * b)
+}
+def swap(p: tuple<bool, list<int>>) {
+(p.1, p.0)
+}
+val (q, r) = divmod(17, 5)
+print((q, r))
+val named: tuple<string, float, bool> = ("pi", 3.14, true)
+print(named.0 + "!")
+print(swap((named.1 == 3.14, [1])))
+print(((1,), ("a", 2).1))
+"#,
+        );
+        assert!(code
+            .contains("std::tuple<bdl::Int,bdl::Int> bdl_divmod(bdl::Int bdl_a, bdl::Int bdl_b)"));
+        assert!(code.contains("auto [bdl_q, bdl_r] = bdl_divmod(bdl::Int(17), bdl::Int(5));"));
+        assert!(code.contains("std::get<0>(bdl_named)"));
+        if let Some(output) = run(&code) {
+            assert_eq!(output, "(3, 2)\npi!\n([1], True)\n((1,), 2)\n");
+        }
+    }
+
+    #[test]
+    fn test_structs() {
+        let source = r#"struct Line { start: Point, end: Point, label: string }
+struct Point { x: int, y: float }
+struct Tree { value: int, children: list<Tree> }
+struct Unit {}
+def shifted(p: Point): Point {
+Point(p.x + 1, p.y)
+}
+val a = Point(1, 2.5)
+var b = shifted(a)
+print(b)
+print(a == b)
+b.x = b.x - 1
+print(a == b)
+var l = Line(a, b, "diagonal")
+l.end.y = 0.5
+print(l)
+print([Tree(1, [Tree(2, [])])])
+print((Unit(), l.start.x))
+"#;
+        let code = generate_source(source);
+        let line = code.find("struct bdl_Line {").unwrap();
+        assert!(code.find("struct bdl_Point {").unwrap() < line);
+        assert!(code.contains("bdl_b.bdl_x = (bdl_b.bdl_x - bdl::Int(1));"));
+        let expected = "Point(x=2, y=2.5)\nFalse\nTrue\n\
+                        Line(start=Point(x=1, y=2.5), end=Point(x=1, y=0.5), label='diagonal')\n\
+                        [Tree(value=1, children=[Tree(value=2, children=[])])]\n(Unit(), 1)\n";
+        if let Some(output) = run(&code) {
+            assert_eq!(output, expected);
+        }
+        if let Some(output) = run(&generate_source_with(source, ArithMode::Checked)) {
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn test_cpp_names_are_mangled() {
+        let source = r#"struct class { new: int, bdl_x: int }
+enum auto { default, this(int: int) }
+def double(int: int): int {
+int * 2
+}
+def main(auto: auto): int {
+match auto {
+this(void) => void
+default => 0
+}
+}
+val namespace = class(1, 2)
+var delete = [namespace.new, namespace.bdl_x]
+val std = (bdl: int) => bdl + delete[0]
+for (long, short) in enumerate(delete) {
+print((long, double(short), std(short)))
+}
+print((namespace, main(this(7)), main(default)))
+def rand(): int {
+4
+}
+val NAN = rand()
+val INFINITY = NAN * 2
+val requires = (concept: int) => concept + INFINITY
+print((NAN, INFINITY, requires(1)))
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("struct bdl_class {"));
+        assert!(code.contains("bdl::Int bdl_double(bdl::Int bdl_int)"));
+        assert!(code.contains("bdl_bdl_x"));
+        let expected = "(0, 2, 2)\n(1, 4, 3)\n\
+                        (class(new=1, bdl_x=2), 7, 0)\n(4, 8, 9)\n";
+        assert_eq!(run(&code).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_control_flow_in_match_values() {
+        let source = r#"enum Shape { Circle(r: int), Empty }
+def size(s: Shape): int {
+val r = match s {
+Circle(r) => r
+Empty => return 0 - 1
+}
+r * 10
+}
+print((size(Circle(2)), size(Empty)))
+var found = 0
+for s in [Circle(1), Empty, Circle(3), Circle(5), Circle(7)] {
+found += match s {
+Circle(r) if r > 4 => break
+Circle(r) => r
+Empty => continue
+}
+}
+print(found)
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("std::optional<bdl::Int> value_"));
+        assert_eq!(run(&code).unwrap(), "(20, -1)\n4\n");
+    }
+
+    #[test]
+    fn test_enums_and_match() {
+        let source = r#"enum Shape { Circle(r: float), Rect(w: float, h: float), Empty }
+enum Tree { Leaf, Node(left: Tree, value: int, right: Tree) }
+struct Scene { shapes: list<Shape>, tree: Tree }
+def area(s: Shape): float {
+match s {
+Circle(r) => r * r * 3.0
+Rect(w, h) if w == h => w * w
+Rect(w, h) => w * h
+Empty => 0.0
+}
+}
+def total(t: Tree): int {
+match t {
+Leaf => 0
+Node(l, v, r) => total(l) + v + total(r)
+}
+}
+def describe(n: int) {
+match n {
+0 => print("zero")
+n if n > 0 => {
+print("positive")
+print(n)
+}
+_ => print("negative")
+}
+}
+val t = Node(Node(Leaf, 1, Leaf), 2, Leaf)
+print(total(t))
+print(t)
+print([area(Circle(1.0)), area(Rect(2.0, 2.0)), area(Rect(2.0, 3.0)), area(Empty)])
+print(Scene([Empty, Circle(0.5)], Leaf))
+print(Circle(1.0) == Circle(1.0))
+print(Rect(1.0, 2.0) == Rect(2.0, 1.0))
+describe(3)
+describe(0)
+val word = match t {
+Node(Node(_, v, _), _, _) if v > 5 => "big"
+Node(Node(_, v, _), _, _) => "small"
+_ => "other"
+}
+print(word)
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("using Variant = std::variant<bdl_Circle, bdl_Rect, bdl_Empty>;"));
+        assert!(code.contains("std::holds_alternative<bdl_Shape::bdl_Circle>"));
+        let expected = "3\nNode(left=Node(left=Leaf, value=1, right=Leaf), value=2, right=Leaf)\n\
+                        [3.0, 4.0, 6.0, 0.0]\nScene(shapes=[Empty, Circle(r=0.5)], tree=Leaf)\n\
+                        True\nFalse\npositive\n3\nzero\nsmall\n";
+        if let Some(output) = run(&code) {
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn test_lambdas() {
+        let source = r#"struct Counter { n: int }
+def call_with(f, x) {
+f(x)
+}
+def adder(n: int): (int) -> int {
+(x: int) => x + n
+}
+def compose(f: (int) -> int, g: (int) -> int): (int) -> int {
+(x: int) => f(g(x))
+}
+val k = 10
+var total = Counter(0)
+val twice = (x) => x * 2
+val add_k = (x: int) => x + k
+val bump = (d: int) => {
+total.n = total.n + d
+total.n
+}
+print(call_with(twice, 4))
+print(add_k(1))
+bump(5)
+bump(6)
+print(total.n)
+val add3 = adder(3)
+print(add3(4))
+val composed = compose(twice, adder(1))
+print(composed(5))
+val nested = (a: int) => (b: int) => a + b + k
+print(call_with(nested(1), 2))
+var f: (int) -> int = twice
+print(f(1))
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("auto bdl_add_k = [bdl_k](const bdl::Int &bdl_x) -> bdl::Int {"));
+        assert!(code.contains("auto bdl_bump = [box_bdl_total, &bdl_total = *box_bdl_total](const bdl::Int &bdl_d) -> bdl::Int {"));
+        assert!(code.contains("std::function<bdl::Int(bdl::Int)> bdl_f;"));
+        if let Some(output) = run(&code) {
+            assert_eq!(output, "8\n11\n11\n7\n12\n13\n2\n");
+        }
+    }
+
+    #[test]
+    fn test_shared_vars_outlive_their_block() {
+        let source = r#"def counter(): () -> int {
+var n = 0
+() => {
+n += 1
+n
+}
+}
+def pair(): tuple<(int) -> int, () -> int> {
+var (total, calls) = (0, 0)
+val add = (d: int) => {
+total += d
+calls += 1
+total
+}
+(add, () => calls)
+}
+val next = counter()
+next()
+next()
+print(next())
+val other = counter()
+print((other(), next()))
+val (add, calls) = pair()
+add(5)
+val sum = add(2)
+print((sum, calls()))
+var readers: list<() -> int> = []
+for i in 0..3 {
+var seen = i * 10
+push(readers, () => seen)
+seen += 1
+}
+print(map(readers, (r: () -> int) => r()))
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("auto box_bdl_n = std::make_shared<bdl::Int>();"));
+        let expected = "3\n(1, 4)\n(7, 2)\n[1, 11, 21]\n";
+        assert_eq!(run(&code).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_recursive_var_lambdas_can_escape() {
+        let source = r#"def countdown(): (int) -> list<int> {
+var down: (int) -> list<int> = (n: int) => []
+down = (n: int) => {
+if n == 0 {
+return [0]
+}
+var rest = down(n - 1)
+push(rest, n)
+rest
+}
+down
+}
+val f = countdown()
+print(f(3))
+var fs: list<(int) -> list<int>> = []
+rep 2 {
+push(fs, countdown())
+}
+print(map(fs, (g: (int) -> list<int>) => g(2)))
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("auto box_bdl_down = std::make_shared<"));
+        let expected = "[0, 1, 2, 3]\n[[0, 1, 2], [0, 1, 2]]\n";
+        assert_eq!(run(&code).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_lambdas_in_generic_functions() {
+        let source = r#"def compose(f, g) {
+(x) => g(f(x))
+}
+def twice_then(f, x) {
+val again = (y) => f(f(y))
+again(x)
+}
+val inc = compose((n: int) => n + 1, (n: int) => n * 2)
+val shout = compose((s: string) => s + "!", (s: string) => [s, s])
+val both = compose(inc, inc)
+print((inc(3), shout("hi"), both(1), twice_then((n: int) => n * 3, 2)))
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("return [bdl_f, bdl_g](const auto &bdl_x) -> auto {"));
+        assert_eq!(run(&code).unwrap(), "(8, ['hi!', 'hi!'], 10, 18)\n");
+    }
+
+    #[test]
+    fn test_recursive_lambdas() {
+        let source = r#"def counter(start: int): (int) -> int {
+val go = (n: int) => {
+if n <= start {
+return 0
+}
+1 + go(n - 1)
+}
+go
+}
+val k = 1
+val fact = (n: int) => {
+if n < 2 {
+return k
+}
+n * fact(n - 1)
+}
+print(fact(20))
+print(fact(30))
+var fib: (int) -> int = (n: int) => {
+if n < 2 {
+return n
+}
+fib(n - 1) + fib(n - 2)
+}
+print(fib(15))
+val c = counter(3)
+print(c(10))
+val even = (n: int) => {
+val odd = (m: int) => !even(m)
+if n == 0 {
+return true
+}
+odd(n - 1)
+}
+print(even(10))
+"#;
+        let code = generate_source(source);
+        assert!(
+            code.contains("auto bdl_fact = bdl::fix([bdl_k](auto &&bdl_fact, const bdl::Int &bdl_n) -> bdl::Int {")
+        );
+        assert!(code.contains(
+            "bdl_fib = [box_bdl_fib, &bdl_fib = *box_bdl_fib](const bdl::Int &bdl_n) -> bdl::Int {"
+        ));
+        if let Some(output) = run(&code) {
+            let expected = "2432902008176640000\n265252859812191058636308480000000\n610\n7\nTrue\n";
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn test_list_builtins() {
+        let source = r#"struct P { x: int, y: int }
+def sum_all(ys: list<list<int>>) {
+map(ys, (y) => sum(y))
+}
+def map_twice(xs: list<int>, f: (int) -> int) {
+map(map(xs, f), f)
+}
+val xs = [5, 3, 8, 1]
+val k = 2
+print(map(xs, (x) => x * k))
+print(filter(xs, (x) => x > 2))
+print(reduce(xs, (a, b) => a + b))
+print(fold(xs, 0.5, (acc, x) => acc + x))
+print([any(xs, (x) => x > 7), all(xs, (x) => x > 7)])
+print(zip(xs, ["a", "b", "c"]))
+print(map(enumerate(xs), (p) => p.0 * p.1))
+print([sum(xs), min(xs), max(xs)])
+print(sum([1.5, 2.5]))
+print(sorted(["b", "c", "a"]))
+print(map(map(xs, (x) => P(x, x * x)), (p) => p.y))
+print(sum_all([[1, 2], [3]]))
+print(map_twice(xs, (x) => x + 1))
+val empty: list<int> = []
+print(max(empty))
+"#;
+        let code = generate_source(source);
+        assert!(
+            code.contains("bdl::list::map(bdl_xs, [bdl_k](const bdl::Int &bdl_x) -> bdl::Int {")
+        );
+        assert!(code.contains(r#"bdl::list::max(bdl_empty, "test.bdl:24:7")"#));
+        let expected = "[10, 6, 16, 2]\n[5, 3, 8]\n17\n17.5\n[True, False]\n\
+                        [(5, 'a'), (3, 'b'), (8, 'c')]\n[0, 3, 16, 3]\n[17, 1, 8]\n4.0\n\
+                        ['a', 'b', 'c']\n[25, 9, 64, 1]\n[3, 3]\n[7, 5, 10, 3]\n";
+        for arith in [ArithMode::BigInt, ArithMode::Checked, ArithMode::Release] {
+            if let Some(output) = execute(&generate_source_with(source, arith)) {
+                assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+                assert_eq!(
+                    String::from_utf8(output.stderr).unwrap(),
+                    "test.bdl:24:7: error: `max` of an empty list\n"
+                );
+            }
+        }
+
+        // `sum` checks for overflow like `+` does
+        let code =
+            generate_source_with("print(sum([9223372036854775807, 1]))\n", ArithMode::Checked);
+        if let Some(output) = execute(&code) {
+            assert_eq!(
+                String::from_utf8(output.stderr).unwrap(),
+                "test.bdl:1:7: error: integer overflow in `+`\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_builtins_can_be_shadowed() {
+        let source = r#"def map(xs: list<int>, f: (int) -> int) {
+print("mine")
+xs
+}
+print(map([1], (x) => x + 1))
+"#;
+        let code = generate_source(source);
+        assert!(!code.contains("bdl::list::map("));
+        if let Some(output) = run(&code) {
+            assert_eq!(output, "mine\n[1]\n");
+        }
+    }
+
+    #[test]
+    fn test_reassignments() {
+        let source = r#"struct P { x: int, y: float }
+var xs = [1, 2, 3]
+xs[0] = 10
+xs[1] += 5
+xs[2] *= xs[0]
+print(xs)
+var n = 7
+n -= 2
+n /= 2
+var f = 1.5
+f += 1
+f *= 2
+print((n, f))
+var p = P(1, 2.0)
+p.x += 4
+p.y /= 4
+print(p)
+var grid = [[0, 0], [0, 0]]
+grid[1][0] = 5
+grid[0][1] += grid[1][0] + 1
+print(grid)
+var flags = [false, false]
+flags[1] = true
+var s = "a"
+s += "b"
+var t = (1, "x")
+t.0 += 1
+print((flags, s, t, [4, 5, 6][2]))
+var calls = 0
+val next = () => {
+calls += 1
+calls - 1
+}
+var ys = [0, 0]
+ys[next()] += 10
+ys[next()] -= 1
+print((ys, calls))
+print(xs[3])
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("bdl_n = (bdl_n - bdl::Int(2));"));
+        let expected = "[10, 7, 30]\n(2, 5.0)\nP(x=5, y=0.5)\n[[0, 6], [5, 0]]\n\
+                        ([False, True], 'ab', (2, 'x'), 6)\n([10, -1], 2)\n";
+        for arith in [ArithMode::BigInt, ArithMode::Checked, ArithMode::Release] {
+            if let Some(output) = execute(&generate_source_with(source, arith)) {
+                assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+                assert_eq!(
+                    String::from_utf8(output.stderr).unwrap(),
+                    "test.bdl:38:7: error: index 3 is out of range for a list of length 3\n"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_const_vals() {
+        let source = r#"struct P { x: int, y: float }
+val n = 3
+val p = P(n, 0.5)
+val (q, r) = (n / 2, n - 2)
+val twice = (x: int) => x * 2
+var xs = [n]
+push(xs, twice(q + r))
+push(xs, p.x)
+val last = pop(xs)
+print((xs, last, xs[1]))
+var empty = [1]
+pop(empty)
+pop(empty)
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("const bdl::Int bdl_n = bdl::Int(3);"));
+        assert!(code.contains("const bdl_P bdl_p = bdl_P(bdl_n, 0.5);"));
+        assert!(code.contains("const auto [bdl_q, bdl_r] = "));
+        assert!(code.contains("const auto bdl_twice = "));
+        assert!(code.contains("std::vector<bdl::Int> bdl_xs;"));
+        if let Some(output) = execute(&code) {
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                "([3, 4], 3, 4)\n"
+            );
+            assert_eq!(
+                String::from_utf8(output.stderr).unwrap(),
+                "test.bdl:13:1: error: `pop` of an empty list\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rep() {
+        let source = r#"def times(n: int) {
+return n * 2
+}
+var total = 0
+rep times(3) {
+total += 1
+rep -(-2) {
+total *= 2
+}
+}
+rep 0 {
+print("never")
+}
+var n = 3
+rep n {
+n -= 1
+}
+print((total, n))
+"#;
+        for arith in [ArithMode::BigInt, ArithMode::Checked, ArithMode::Release] {
+            if let Some(output) = run(&generate_source_with(source, arith)) {
+                assert_eq!(output, "(5460, 0)\n");
+            }
+        }
+    }
+
+    #[test]
+    fn test_loops() {
+        let source = r#"var n = 0
+while n < 10 {
+n += 1
+if n == 2 {
+continue
+}
+if n == 5 {
+break
+}
+print(n)
+}
+var skipped = 0
+rep 4 {
+skipped += 1
+continue
+print("never")
+}
+var pairs = []
+outer: rep 3 {
+var j = 0
+inner: while true {
+j += 1
+val k = j * 10
+if j == 2 {
+continue inner
+}
+if j > 3 {
+continue outer
+}
+push(pairs, k)
+}
+}
+var xs = [3, 1, 2]
+while match pop(xs) {
+1 => false
+_ => true
+} {
+print(xs)
+}
+print((skipped, pairs, xs))
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("goto loop_"));
+        let expected = "1\n3\n4\n[3, 1]\n(4, [10, 30, 10, 30, 10, 30], [3])\n";
+        for arith in [ArithMode::BigInt, ArithMode::Checked, ArithMode::Release] {
+            if let Some(output) = run(&generate_source_with(source, arith)) {
+                assert_eq!(output, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_for_loops() {
+        let source = r#"def evens(n: int): range {
+return 0..n step 2
+}
+var total = 0
+for x in [1, 2, 3] {
+total += x
+}
+for i in 0..4 {
+total += i * 10
+}
+for i in 10..0 step -3 {
+print(i)
+}
+val r = evens(7)
+for i in r {
+total += i * 100
+}
+for (i, s) in enumerate(["a", "b"]) {
+print((i, s))
+}
+for (n, s) in zip([1, 2], ["x", "y"]) {
+print((n, s))
+}
+var xs = [1]
+for x in xs {
+if x < 4 {
+push(xs, x + 1)
+}
+}
+var pairs = []
+outer: for i in 0..3 {
+for j in 0..3 {
+if j > i {
+continue outer
+}
+push(pairs, (i, j))
+}
+}
+for i in 5..5 {
+print("never")
+}
+print((total, xs, pairs, r, 0..3))
+for i in 0..3 step 0 {
+print(i)
+}
+"#;
+        let code = generate_source(source);
+        assert!(code.contains("bdl::each_enumerated("));
+        let expected = "10\n7\n4\n1\n(0, 'a')\n(1, 'b')\n(1, 'x')\n(2, 'y')\n\
+                        (1266, [1, 2, 3, 4], [(0, 0), (1, 0), (1, 1), (2, 0), (2, 1), (2, 2)], \
+                        range(0, 7, 2), range(0, 3))\n";
+        for arith in [ArithMode::BigInt, ArithMode::Checked, ArithMode::Release] {
+            if let Some(output) = execute(&generate_source_with(source, arith)) {
+                assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+                assert_eq!(
+                    String::from_utf8(output.stderr).unwrap(),
+                    "test.bdl:43:10: error: the step of a range cannot be zero\n"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_for_loops_read_elements_in_place() {
+        let source = r#"val words = ["a", "b"]
+for (i, w) in enumerate(words) {
+print((i, w))
+}
+"#;
+        let mut code = generate_source(source);
+        assert!(code.contains("for (const auto &[bdl_i, bdl_w] : bdl::each_enumerated(bdl_words))"));
+        // Only a list the body could push to is read a copy at a time
+        code.push_str(
+            r#"
+using Strings = std::vector<std::string>;
+template <typename V>
+using ElementOf = decltype(*bdl::each(std::declval<V>()).begin());
+static_assert(std::is_same_v<ElementOf<const Strings &>, const std::string &>);
+static_assert(std::is_same_v<ElementOf<Strings>, const std::string &>);
+static_assert(std::is_same_v<ElementOf<Strings &>, std::string>);
+static_assert(std::is_same_v<ElementOf<const std::vector<bool> &>, bool>);
+static_assert(std::is_same_v<decltype(*bdl::each(bdl::Range{}).begin()), const bdl::Int &>);
+"#,
+        );
+        if let Some(output) = run(&code) {
+            assert_eq!(output, "(0, 'a')\n(1, 'b')\n");
+        }
+    }
+}
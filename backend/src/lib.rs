@@ -1,2 +1,5 @@
 pub mod codegen;
+mod runtime;
 pub use codegen::*;
+#[cfg(test)]
+mod codegen_test;
@@ -1,21 +1,46 @@
 mod codegen;
+mod runtime;
 
-use bdl_frontend::ast::{Expr, IntegerLiteral, PrintExpr, Program};
-use bdl_frontend::parser::parse_program;
-use codegen::generate;
+use bdl_frontend::parser::parse_source;
+use bdl_frontend::typeck::check_program;
+use codegen::{generate, ArithMode, Options};
 use std::{env, fs};
 
 fn main() {
-    // Create a simple test program that prints a number
-    let file = env::args().nth(1).expect("No file provided");
-    let src = fs::read_to_string(file).expect("Failed to read file");
-    let prog = parse_program(&src)
-        .expect("Failed to parse program")
-        .Program()
-        .unwrap();
+    // `--checked-arith` and `--release` swap arbitrary precision ints for
+    // 64-bit ones, with and without overflow checks
+    let mut arith = ArithMode::BigInt;
+    let mut file = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--checked-arith" => arith = ArithMode::Checked,
+            "--release" => arith = ArithMode::Release,
+            _ => file = Some(arg),
+        }
+    }
+    let file = file.expect("No file provided");
+    let src = fs::read_to_string(&file).expect("Failed to read file");
+    let mut prog = match parse_source(&file, &src) {
+        Ok(ast) => ast.Program().unwrap(),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(errors) = check_program(&mut prog) {
+        for e in errors {
+            eprintln!("{}", e.render(&file, &src));
+        }
+        std::process::exit(1);
+    }
 
     // Create and run the processor
-    let program = generate(&prog);
+    let options = Options {
+        arith,
+        file: file.clone(),
+    };
+    let program = generate(&prog, &options);
 
     println!("{}", program);
 }
@@ -0,0 +1,7 @@
+// C++ support code for generated programs. It lives in `runtime/` as real
+// headers so that it can be read and edited as C++
+pub const INT: &str = include_str!("runtime/int.hpp");
+pub const INT64: &str = include_str!("runtime/int64.hpp");
+pub const PRELUDE: &str = include_str!("runtime/prelude.hpp");
+pub const LIST: &str = include_str!("runtime/list.hpp");
+pub const LOOPS: &str = include_str!("runtime/loops.hpp");
@@ -0,0 +1,387 @@
+// BDL `int`: a 64-bit integer that moves to arbitrary precision when a
+// result does not fit, and back again once it does
+namespace bdl {
+
+[[noreturn]] inline void panic(const std::string &message) {
+    std::cerr << "error: " << message << '\n';
+    std::exit(1);
+}
+
+class Int {
+public:
+    Int() = default;
+
+    template <typename T, typename = std::enable_if_t<std::is_integral_v<T>>>
+    Int(T value) {
+        if constexpr (std::is_unsigned_v<T>) {
+            if (uint64_t(value) > uint64_t(INT64_MAX)) {
+                set_big(false, from_u64(value));
+                return;
+            }
+        }
+        small_ = int64_t(value);
+    }
+
+    // Decimal digits, optionally preceded by a minus sign
+    explicit Int(const char *digits) {
+        bool negative = *digits == '-';
+        if (negative) {
+            digits++;
+        }
+        Limbs magnitude;
+        for (; *digits; digits++) {
+            magnitude = add(mul_small(magnitude, 10), from_u64(*digits - '0'));
+        }
+        set_big(negative, std::move(magnitude));
+    }
+
+    std::string to_string() const {
+        if (!is_big()) {
+            return std::to_string(small_);
+        }
+        std::string text = std::to_string(limbs_.back());
+        for (size_t i = limbs_.size() - 1; i-- > 0;) {
+            std::string limb = std::to_string(limbs_[i]);
+            text += std::string(9 - limb.size(), '0') + limb;
+        }
+        return negative_ ? "-" + text : text;
+    }
+
+    // Only meaningful for a value that fits, which the caller checks first
+    explicit operator int64_t() const { return small_; }
+
+    double to_double() const {
+        if (!is_big()) {
+            return double(small_);
+        }
+        double value = 0;
+        for (size_t i = limbs_.size(); i-- > 0;) {
+            value = value * BASE + limbs_[i];
+        }
+        return negative_ ? -value : value;
+    }
+
+    friend Int operator+(const Int &a, const Int &b) {
+        int64_t result;
+        if (!a.is_big() && !b.is_big() && !__builtin_add_overflow(a.small_, b.small_, &result)) {
+            return Int(result);
+        }
+        return signed_add(a.negative(), a.magnitude(), b.negative(), b.magnitude());
+    }
+
+    friend Int operator-(const Int &a, const Int &b) {
+        int64_t result;
+        if (!a.is_big() && !b.is_big() && !__builtin_sub_overflow(a.small_, b.small_, &result)) {
+            return Int(result);
+        }
+        return signed_add(a.negative(), a.magnitude(), !b.negative(), b.magnitude());
+    }
+
+    friend Int operator*(const Int &a, const Int &b) {
+        int64_t result;
+        if (!a.is_big() && !b.is_big() && !__builtin_mul_overflow(a.small_, b.small_, &result)) {
+            return Int(result);
+        }
+        Int product;
+        product.set_big(a.negative() != b.negative(), mul(a.magnitude(), b.magnitude()));
+        return product;
+    }
+
+    // Rounds towards zero, as C++ does
+    friend Int operator/(const Int &a, const Int &b) {
+        if (b.is_zero()) {
+            panic("integer division by zero");
+        }
+        if (!a.is_big() && !b.is_big() && !(a.small_ == INT64_MIN && b.small_ == -1)) {
+            return Int(a.small_ / b.small_);
+        }
+        Int quotient;
+        quotient.set_big(a.negative() != b.negative(), divide(a.magnitude(), b.magnitude()));
+        return quotient;
+    }
+
+    Int operator-() const { return Int(0) - *this; }
+
+    Int &operator+=(const Int &other) { return *this = *this + other; }
+    Int &operator-=(const Int &other) { return *this = *this - other; }
+    Int &operator*=(const Int &other) { return *this = *this * other; }
+    Int &operator/=(const Int &other) { return *this = *this / other; }
+
+    friend bool operator==(const Int &a, const Int &b) { return compare(a, b) == 0; }
+    friend bool operator!=(const Int &a, const Int &b) { return compare(a, b) != 0; }
+    friend bool operator<(const Int &a, const Int &b) { return compare(a, b) < 0; }
+    friend bool operator>(const Int &a, const Int &b) { return compare(a, b) > 0; }
+    friend bool operator<=(const Int &a, const Int &b) { return compare(a, b) <= 0; }
+    friend bool operator>=(const Int &a, const Int &b) { return compare(a, b) >= 0; }
+
+private:
+    // Magnitudes are base 10^9 digits, least significant first, with no
+    // leading zeros
+    using Limbs = std::vector<uint32_t>;
+    static constexpr uint32_t BASE = 1000000000;
+
+    // Used while the value fits, in which case `limbs_` is empty
+    int64_t small_ = 0;
+    bool negative_ = false;
+    Limbs limbs_;
+
+    bool is_big() const { return !limbs_.empty(); }
+    bool is_zero() const { return !is_big() && small_ == 0; }
+    bool negative() const { return is_big() ? negative_ : small_ < 0; }
+
+    Limbs magnitude() const {
+        if (is_big()) {
+            return limbs_;
+        }
+        // Negating INT64_MIN overflows, so go through the unsigned type
+        uint64_t value = small_ < 0 ? 0 - uint64_t(small_) : uint64_t(small_);
+        return from_u64(value);
+    }
+
+    // Stores `magnitude` with the given sign, going back to `small_` when
+    // it fits
+    void set_big(bool negative, Limbs magnitude) {
+        trim(magnitude);
+        if (magnitude.size() <= 3) {
+            unsigned __int128 value = 0;
+            for (size_t i = magnitude.size(); i-- > 0;) {
+                value = value * BASE + magnitude[i];
+            }
+            if (value <= uint64_t(INT64_MAX) || (negative && value == uint64_t(INT64_MAX) + 1)) {
+                small_ = negative ? int64_t(0 - uint64_t(value)) : int64_t(value);
+                negative_ = false;
+                limbs_.clear();
+                return;
+            }
+        }
+        small_ = 0;
+        negative_ = negative;
+        limbs_ = std::move(magnitude);
+    }
+
+    static Int signed_add(bool a_negative, const Limbs &a, bool b_negative, const Limbs &b) {
+        Int sum;
+        if (a_negative == b_negative) {
+            sum.set_big(a_negative, add(a, b));
+        } else if (compare_magnitudes(a, b) >= 0) {
+            sum.set_big(a_negative, sub(a, b));
+        } else {
+            sum.set_big(b_negative, sub(b, a));
+        }
+        return sum;
+    }
+
+    static int compare(const Int &a, const Int &b) {
+        if (!a.is_big() && !b.is_big()) {
+            return (a.small_ > b.small_) - (a.small_ < b.small_);
+        }
+        if (a.negative() != b.negative()) {
+            return a.negative() ? -1 : 1;
+        }
+        int order = compare_magnitudes(a.magnitude(), b.magnitude());
+        return a.negative() ? -order : order;
+    }
+
+    static void trim(Limbs &limbs) {
+        while (!limbs.empty() && limbs.back() == 0) {
+            limbs.pop_back();
+        }
+    }
+
+    static Limbs from_u64(uint64_t value) {
+        Limbs limbs;
+        for (; value > 0; value /= BASE) {
+            limbs.push_back(uint32_t(value % BASE));
+        }
+        return limbs;
+    }
+
+    static int compare_magnitudes(const Limbs &a, const Limbs &b) {
+        if (a.size() != b.size()) {
+            return a.size() < b.size() ? -1 : 1;
+        }
+        for (size_t i = a.size(); i-- > 0;) {
+            if (a[i] != b[i]) {
+                return a[i] < b[i] ? -1 : 1;
+            }
+        }
+        return 0;
+    }
+
+    static Limbs add(const Limbs &a, const Limbs &b) {
+        Limbs sum;
+        uint32_t carry = 0;
+        for (size_t i = 0; i < std::max(a.size(), b.size()) || carry; i++) {
+            uint64_t digit = uint64_t(carry) + (i < a.size() ? a[i] : 0) + (i < b.size() ? b[i] : 0);
+            sum.push_back(uint32_t(digit % BASE));
+            carry = uint32_t(digit / BASE);
+        }
+        return sum;
+    }
+
+    // `a - b`, where `a` is at least `b`
+    static Limbs sub(const Limbs &a, const Limbs &b) {
+        Limbs difference;
+        int64_t borrow = 0;
+        for (size_t i = 0; i < a.size(); i++) {
+            int64_t digit = int64_t(a[i]) - borrow - (i < b.size() ? b[i] : 0);
+            borrow = digit < 0;
+            difference.push_back(uint32_t(digit + (borrow ? BASE : 0)));
+        }
+        trim(difference);
+        return difference;
+    }
+
+    static Limbs mul_small(const Limbs &a, uint32_t factor) {
+        Limbs product;
+        uint64_t carry = 0;
+        for (size_t i = 0; i < a.size() || carry; i++) {
+            uint64_t digit = carry + (i < a.size() ? uint64_t(a[i]) * factor : 0);
+            product.push_back(uint32_t(digit % BASE));
+            carry = digit / BASE;
+        }
+        trim(product);
+        return product;
+    }
+
+    static Limbs mul(const Limbs &a, const Limbs &b) {
+        std::vector<uint64_t> digits(a.size() + b.size() + 1);
+        for (size_t i = 0; i < a.size(); i++) {
+            uint64_t carry = 0;
+            for (size_t j = 0; j < b.size() || carry; j++) {
+                uint64_t digit = digits[i + j] + carry + (j < b.size() ? uint64_t(a[i]) * b[j] : 0);
+                digits[i + j] = digit % BASE;
+                carry = digit / BASE;
+            }
+        }
+        Limbs product(digits.begin(), digits.end());
+        trim(product);
+        return product;
+    }
+
+    // Long division, finding each digit of the quotient by bisection
+    static Limbs divide(const Limbs &a, const Limbs &b) {
+        Limbs quotient(a.size());
+        Limbs remainder;
+        for (size_t i = a.size(); i-- > 0;) {
+            remainder.insert(remainder.begin(), a[i]);
+            trim(remainder);
+            uint32_t low = 0, high = BASE - 1;
+            while (low < high) {
+                uint32_t mid = low + (high - low + 1) / 2;
+                if (compare_magnitudes(mul_small(b, mid), remainder) <= 0) {
+                    low = mid;
+                } else {
+                    high = mid - 1;
+                }
+            }
+            quotient[i] = low;
+            remainder = sub(remainder, mul_small(b, low));
+        }
+        trim(quotient);
+        return quotient;
+    }
+};
+
+// An int meeting a double is promoted to double. These only match actual
+// floating point operands, so that `Int + 1` still stays an `Int`
+template <typename D>
+using Promoted = std::enable_if_t<std::is_floating_point_v<D>, double>;
+template <typename D>
+using Compared = std::enable_if_t<std::is_floating_point_v<D>, bool>;
+
+template <typename D>
+Promoted<D> operator+(const Int &a, D b) {
+    return a.to_double() + b;
+}
+template <typename D>
+Promoted<D> operator+(D a, const Int &b) {
+    return a + b.to_double();
+}
+
+template <typename D>
+Promoted<D> operator-(const Int &a, D b) {
+    return a.to_double() - b;
+}
+template <typename D>
+Promoted<D> operator-(D a, const Int &b) {
+    return a - b.to_double();
+}
+
+template <typename D>
+Promoted<D> operator*(const Int &a, D b) {
+    return a.to_double() * b;
+}
+template <typename D>
+Promoted<D> operator*(D a, const Int &b) {
+    return a * b.to_double();
+}
+
+template <typename D>
+Promoted<D> operator/(const Int &a, D b) {
+    return a.to_double() / b;
+}
+template <typename D>
+Promoted<D> operator/(D a, const Int &b) {
+    return a / b.to_double();
+}
+
+template <typename D>
+Compared<D> operator==(const Int &a, D b) {
+    return a.to_double() == b;
+}
+template <typename D>
+Compared<D> operator==(D a, const Int &b) {
+    return a == b.to_double();
+}
+
+template <typename D>
+Compared<D> operator!=(const Int &a, D b) {
+    return a.to_double() != b;
+}
+template <typename D>
+Compared<D> operator!=(D a, const Int &b) {
+    return a != b.to_double();
+}
+
+template <typename D>
+Compared<D> operator<(const Int &a, D b) {
+    return a.to_double() < b;
+}
+template <typename D>
+Compared<D> operator<(D a, const Int &b) {
+    return a < b.to_double();
+}
+
+template <typename D>
+Compared<D> operator>(const Int &a, D b) {
+    return a.to_double() > b;
+}
+template <typename D>
+Compared<D> operator>(D a, const Int &b) {
+    return a > b.to_double();
+}
+
+template <typename D>
+Compared<D> operator<=(const Int &a, D b) {
+    return a.to_double() <= b;
+}
+template <typename D>
+Compared<D> operator<=(D a, const Int &b) {
+    return a <= b.to_double();
+}
+
+template <typename D>
+Compared<D> operator>=(const Int &a, D b) {
+    return a.to_double() >= b;
+}
+template <typename D>
+Compared<D> operator>=(D a, const Int &b) {
+    return a >= b.to_double();
+}
+
+inline void write_repr(std::ostream &os, const Int &value) {
+    os << value.to_string();
+}
+
+}  // namespace bdl
@@ -0,0 +1,57 @@
+// BDL `int` in the fixed-width build modes: a plain 64-bit integer, with
+// helpers that the checked mode calls in place of `+ - *`
+namespace bdl {
+
+[[noreturn]] inline void panic(const std::string &message) {
+    std::cerr << "error: " << message << '\n';
+    std::exit(1);
+}
+
+using Int = int64_t;
+
+[[noreturn]] inline void overflow(const char *op, const char *location) {
+    std::cerr << location << ": error: integer overflow in `" << op << "`\n";
+    std::abort();
+}
+
+// Anything but a pair of ints keeps its usual meaning
+template <typename A, typename B>
+auto checked_add(const A &a, const B &b, const char *) {
+    return a + b;
+}
+
+template <typename A, typename B>
+auto checked_sub(const A &a, const B &b, const char *) {
+    return a - b;
+}
+
+template <typename A, typename B>
+auto checked_mul(const A &a, const B &b, const char *) {
+    return a * b;
+}
+
+inline Int checked_add(Int a, Int b, const char *location) {
+    Int result;
+    if (__builtin_add_overflow(a, b, &result)) {
+        overflow("+", location);
+    }
+    return result;
+}
+
+inline Int checked_sub(Int a, Int b, const char *location) {
+    Int result;
+    if (__builtin_sub_overflow(a, b, &result)) {
+        overflow("-", location);
+    }
+    return result;
+}
+
+inline Int checked_mul(Int a, Int b, const char *location) {
+    Int result;
+    if (__builtin_mul_overflow(a, b, &result)) {
+        overflow("*", location);
+    }
+    return result;
+}
+
+}  // namespace bdl
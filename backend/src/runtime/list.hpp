@@ -0,0 +1,156 @@
+// BDL built-in list functions. The functions they are given are template
+// parameters, so that a lambda is called directly in the loop instead of
+// through a `std::function`. They live in a namespace of their own, which
+// argument-dependent lookup never reaches, so that a program can define a
+// function of the same name
+namespace bdl::list {
+
+[[noreturn]] inline void empty(const char *name, const char *location) {
+    std::cerr << location << ": error: `" << name << "` of an empty list\n";
+    std::exit(1);
+}
+
+// `values[index]`, which can also be assigned to
+template <typename V>
+decltype(auto) at(V &&values, const Int &index, const char *location) {
+    if (index < Int(0) || index >= Int(values.size())) {
+        std::cerr << location << ": error: index ";
+        write_repr(std::cerr, index);
+        std::cerr << " is out of range for a list of length " << values.size() << '\n';
+        std::exit(1);
+    }
+    return values[size_t(int64_t(index))];
+}
+
+template <typename T, typename F>
+auto map(const std::vector<T> &values, F f) {
+    std::vector<std::decay_t<decltype(f(values[0]))>> result;
+    result.reserve(values.size());
+    for (const T &value : values) {
+        result.push_back(f(value));
+    }
+    return result;
+}
+
+template <typename T, typename F>
+std::vector<T> filter(const std::vector<T> &values, F keep) {
+    std::vector<T> result;
+    for (const T &value : values) {
+        if (keep(value)) {
+            result.push_back(value);
+        }
+    }
+    return result;
+}
+
+// Combines the values from the left, starting from the first of them
+template <typename T, typename F>
+T reduce(const std::vector<T> &values, F f, const char *location) {
+    if (values.empty()) {
+        empty("reduce", location);
+    }
+    T result = values[0];
+    for (size_t i = 1; i < values.size(); i++) {
+        result = f(result, values[i]);
+    }
+    return result;
+}
+
+template <typename T, typename A, typename F>
+A fold(const std::vector<T> &values, A initial, F f) {
+    for (const T &value : values) {
+        initial = f(initial, value);
+    }
+    return initial;
+}
+
+template <typename T, typename F>
+bool any(const std::vector<T> &values, F test) {
+    for (const T &value : values) {
+        if (test(value)) {
+            return true;
+        }
+    }
+    return false;
+}
+
+template <typename T, typename F>
+bool all(const std::vector<T> &values, F test) {
+    for (const T &value : values) {
+        if (!test(value)) {
+            return false;
+        }
+    }
+    return true;
+}
+
+// Stops at the end of the shorter list, as Python does
+template <typename A, typename B>
+std::vector<std::tuple<A, B>> zip(const std::vector<A> &a, const std::vector<B> &b) {
+    std::vector<std::tuple<A, B>> result;
+    result.reserve(std::min(a.size(), b.size()));
+    for (size_t i = 0; i < a.size() && i < b.size(); i++) {
+        result.emplace_back(a[i], b[i]);
+    }
+    return result;
+}
+
+template <typename T>
+std::vector<std::tuple<Int, T>> enumerate(const std::vector<T> &values) {
+    std::vector<std::tuple<Int, T>> result;
+    result.reserve(values.size());
+    for (size_t i = 0; i < values.size(); i++) {
+        result.emplace_back(Int(i), values[i]);
+    }
+    return result;
+}
+
+// `add` is `+`, or the overflow checking version of it
+template <typename T, typename Add>
+T sum(const std::vector<T> &values, Add add) {
+    T result{};
+    for (const T &value : values) {
+        result = add(result, value);
+    }
+    return result;
+}
+
+template <typename T>
+T min(const std::vector<T> &values, const char *location) {
+    if (values.empty()) {
+        empty("min", location);
+    }
+    return *std::min_element(values.begin(), values.end());
+}
+
+template <typename T>
+T max(const std::vector<T> &values, const char *location) {
+    if (values.empty()) {
+        empty("max", location);
+    }
+    return *std::max_element(values.begin(), values.end());
+}
+
+template <typename T>
+std::vector<T> sorted(std::vector<T> values) {
+    std::sort(values.begin(), values.end());
+    return values;
+}
+
+template <typename T, typename U>
+void push(std::vector<T> &values, U &&value) {
+    values.push_back(std::forward<U>(value));
+}
+
+// Removes the last value and gives it back
+template <typename T>
+T pop(std::vector<T> &values, const char *location) {
+    if (values.empty()) {
+        empty("pop", location);
+    }
+    T value = std::move(values.back());
+    values.pop_back();
+    return value;
+}
+
+}  // namespace bdl::list
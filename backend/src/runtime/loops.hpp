@@ -0,0 +1,168 @@
+// What a BDL `for` loop walks: a `range`, or the elements of a list
+namespace bdl {
+
+// The ints from `start` up to, but not including, `stop`, `step` apart. A
+// loop walks it one int at a time, without making a list of it first
+struct Range {
+    Int start;
+    Int stop;
+    Int step;
+
+    struct End {};
+
+    class Iterator {
+    public:
+        explicit Iterator(const Range &range)
+            : current_(range.start),
+              stop_(range.stop),
+              step_(range.step),
+              done_(!range.contains(range.start)) {}
+
+        const Int &operator*() const { return current_; }
+
+        // Stops before stepping past `stop`, which could overflow
+        Iterator &operator++() {
+            bool last = step_ > Int(0) ? current_ >= stop_ - step_ : current_ <= stop_ - step_;
+            if (last) {
+                done_ = true;
+            } else {
+                current_ += step_;
+            }
+            return *this;
+        }
+
+        bool operator!=(End) const { return !done_; }
+
+    private:
+        Int current_;
+        Int stop_;
+        Int step_;
+        bool done_;
+    };
+
+    Iterator begin() const { return Iterator(*this); }
+    End end() const { return {}; }
+
+    bool contains(const Int &value) const {
+        return step > Int(0) ? start <= value && value < stop : stop < value && value <= start;
+    }
+
+    friend bool operator==(const Range &a, const Range &b) {
+        return a.start == b.start && a.stop == b.stop && a.step == b.step;
+    }
+    friend bool operator!=(const Range &a, const Range &b) { return !(a == b); }
+};
+
+inline Range range(Int start, Int stop, Int step, const char *location) {
+    if (step == Int(0)) {
+        std::cerr << location << ": error: the step of a range cannot be zero\n";
+        std::exit(1);
+    }
+    return Range{start, stop, step};
+}
+
+// As Python writes it, leaving out a step of 1
+inline void write_repr(std::ostream &os, const Range &range) {
+    os << "range(";
+    write_repr(os, range.start);
+    os << ", ";
+    write_repr(os, range.stop);
+    if (range.step != Int(1)) {
+        os << ", ";
+        write_repr(os, range.step);
+    }
+    os << ')';
+}
+
+// The elements of a list by their index, so that a loop whose body pushes
+// to the list reads them afresh. A temporary list is kept alive by the view
+template <typename V>
+class Elements {
+    using List = std::remove_reference_t<V>;
+
+public:
+    // Elements are read in place, unless the list is one the loop body could
+    // push to, which may move them while the body still holds one
+    static constexpr bool in_place = !std::is_lvalue_reference_v<V> || std::is_const_v<List>;
+    using Element = std::conditional_t<in_place, typename List::const_reference,
+                                       typename List::value_type>;
+
+    struct End {};
+
+    class Iterator {
+    public:
+        Iterator(const Elements &elements) : elements_(elements) {}
+
+        Element operator*() const { return elements_.values_[index_]; }
+        Iterator &operator++() {
+            index_++;
+            return *this;
+        }
+        bool operator!=(End) const { return index_ < elements_.values_.size(); }
+
+    private:
+        const Elements &elements_;
+        size_t index_ = 0;
+    };
+
+    explicit Elements(V values) : values_(std::forward<V>(values)) {}
+
+    Iterator begin() const { return Iterator(*this); }
+    End end() const { return {}; }
+
+private:
+    V values_;
+};
+
+// As `Elements`, pairing each element with its index as `enumerate` does
+template <typename V>
+class Enumerated {
+public:
+    explicit Enumerated(V values) : elements_(std::forward<V>(values)) {}
+
+    class Iterator {
+    public:
+        explicit Iterator(typename Elements<V>::Iterator it) : it_(it) {}
+
+        std::tuple<Int, typename Elements<V>::Element> operator*() const {
+            return {Int(index_), *it_};
+        }
+        Iterator &operator++() {
+            ++it_;
+            index_++;
+            return *this;
+        }
+        bool operator!=(typename Elements<V>::End end) const { return it_ != end; }
+
+    private:
+        typename Elements<V>::Iterator it_;
+        size_t index_ = 0;
+    };
+
+    Iterator begin() const { return Iterator(elements_.begin()); }
+    typename Elements<V>::End end() const { return {}; }
+
+private:
+    Elements<V> elements_;
+};
+
+inline Range each(Range range) {
+    return range;
+}
+
+// A list the loop can name is walked in place, and a temporary one is moved
+// into the view
+template <typename V>
+using ViewOf = std::conditional_t<std::is_lvalue_reference_v<V>, V, std::decay_t<V>>;
+
+template <typename V>
+Elements<ViewOf<V>> each(V &&values) {
+    return Elements<ViewOf<V>>(std::forward<V>(values));
+}
+
+template <typename V>
+Enumerated<ViewOf<V>> each_enumerated(V &&values) {
+    return Enumerated<ViewOf<V>>(std::forward<V>(values));
+}
+
+}  // namespace bdl
@@ -0,0 +1,144 @@
+// BDL runtime prelude, pasted into every generated program after the
+// standard library includes and `int.hpp`
+namespace bdl {
+
+// Values are formatted the way Python's `repr` would: `print` only differs
+// in writing a string on its own without quotes
+
+// Containers are declared first so that they can nest in any order
+template <typename T>
+void write_repr(std::ostream &os, const std::vector<T> &values);
+template <typename... Ts>
+void write_repr(std::ostream &os, const std::tuple<Ts...> &values);
+
+template <typename T>
+std::enable_if_t<std::is_integral_v<T>> write_repr(std::ostream &os, T value) {
+    os << value;
+}
+
+inline void write_repr(std::ostream &os, bool value) {
+    os << (value ? "True" : "False");
+}
+
+// The shortest digits that read back as the same double, laid out in
+// fixed or scientific notation at the same thresholds as Python
+inline void write_repr(std::ostream &os, double value) {
+    if (std::isnan(value)) {
+        os << "nan";
+        return;
+    }
+    if (std::isinf(value)) {
+        os << (value > 0 ? "inf" : "-inf");
+        return;
+    }
+    char buffer[32];
+    for (int precision = 0; precision < 17; precision++) {
+        std::snprintf(buffer, sizeof buffer, "%.*e", precision, value);
+        if (std::strtod(buffer, nullptr) == value) {
+            break;
+        }
+    }
+    std::string text = buffer;
+    size_t e = text.find('e');
+    int exponent = std::stoi(text.substr(e + 1));
+    std::string digits;
+    for (char c : text.substr(0, e)) {
+        if (std::isdigit(c)) {
+            digits += c;
+        }
+    }
+    if (std::signbit(value)) {
+        os << '-';
+    }
+    if (exponent < -4 || exponent >= 16) {
+        os << digits[0];
+        if (digits.size() > 1) {
+            os << '.' << digits.substr(1);
+        }
+        os << 'e' << (exponent < 0 ? '-' : '+');
+        os << (std::abs(exponent) < 10 ? "0" : "") << std::abs(exponent);
+    } else if (exponent < 0) {
+        os << "0." << std::string(-exponent - 1, '0') << digits;
+    } else if (digits.size() <= size_t(exponent) + 1) {
+        os << digits << std::string(exponent + 1 - digits.size(), '0') << ".0";
+    } else {
+        os << digits.substr(0, exponent + 1) << '.' << digits.substr(exponent + 1);
+    }
+}
+
+inline void write_repr(std::ostream &os, const std::string &value) {
+    char quote = value.find('\'') != std::string::npos && value.find('"') == std::string::npos
+                     ? '"'
+                     : '\'';
+    os << quote;
+    for (char c : value) {
+        switch (c) {
+        case '\\': os << "\\\\"; break;
+        case '\n': os << "\\n"; break;
+        case '\t': os << "\\t"; break;
+        case '\r': os << "\\r"; break;
+        default:
+            if (c == quote) {
+                os << '\\';
+            }
+            os << c;
+        }
+    }
+    os << quote;
+}
+
+template <typename T>
+void write_repr(std::ostream &os, const std::vector<T> &values) {
+    os << '[';
+    const char *separator = "";
+    // Only a `std::vector<bool>` makes a temporary here, of its proxy
+    for (const T &value : values) {
+        os << separator;
+        write_repr(os, value);
+        separator = ", ";
+    }
+    os << ']';
+}
+
+template <typename... Ts>
+void write_repr(std::ostream &os, const std::tuple<Ts...> &values) {
+    os << '(';
+    std::apply(
+        [&os](const auto &...elems) {
+            size_t i = 0;
+            ((os << (i++ > 0 ? ", " : ""), write_repr(os, elems)), ...);
+        },
+        values);
+    // A tuple of one element keeps its trailing comma, as in Python
+    os << (sizeof...(Ts) == 1 ? ",)" : ")");
+}
+
+template <typename T>
+void print(const T &value) {
+    write_repr(std::cout, value);
+    std::cout << '\n';
+}
+
+inline void print(const std::string &value) {
+    std::cout << value << '\n';
+}
+
+// A lambda that calls itself, which it does through the `auto &&` it is
+// given first. Every copy carries its own lambda, so one can be returned or
+// stored without referring back to where it was made
+template <typename F>
+struct Fix {
+    F f;
+
+    template <typename... Args>
+    decltype(auto) operator()(Args &&...args) const {
+        return f(*this, std::forward<Args>(args)...);
+    }
+};
+
+template <typename F>
+Fix<F> fix(F f) {
+    return Fix<F>{std::move(f)};
+}
+
+}  // namespace bdl
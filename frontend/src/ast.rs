@@ -1,3 +1,31 @@
+use std::fmt;
+
+// Location of a node in the source: byte offsets plus the 1-based line and
+// column of `start`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, col: usize) -> Span {
+        Span {
+            start,
+            end,
+            line,
+            col,
+        }
+    }
+
+    // Smallest span covering both `self` and `other`, where `other` ends later
+    pub fn to(&self, other: &Span) -> Span {
+        Span::new(self.start, other.end, self.line, self.col)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Program {
     pub expressions: Vec<Expr>,
@@ -9,37 +37,157 @@ pub struct Program {
 pub struct FunctionDef {
     pub name: Identifier,
     pub args: Vec<TypedIdentifier>,
+    // Annotated in the source, or filled in by the type checker
+    pub return_type: Option<Type>,
+    pub body: Vec<Expr>,
+    pub span: Span,
+}
+
+// `struct Point { x: int, y: int }`. Calling `Point(1, 2)` constructs one
+#[derive(Debug, Clone)]
+pub struct StructDef {
+    pub name: Identifier,
+    pub fields: Vec<TypedIdentifier>,
+    pub span: Span,
+}
+
+impl StructDef {
+    pub fn new(name: Identifier, fields: Vec<TypedIdentifier>, span: Span) -> StructDef {
+        StructDef { name, fields, span }
+    }
+}
+
+// `(x: int) => x * 2`. Unlike a `def`, a lambda can use the variables
+// around it
+#[derive(Debug, Clone)]
+pub struct LambdaExpr {
+    pub params: Vec<TypedIdentifier>,
     pub body: Vec<Expr>,
+    // Filled in by the type checker, along with the captures
+    pub return_type: Type,
+    pub captures: Vec<Capture>,
+    // The `val` holding this lambda, when the lambda calls itself through it
+    pub recursive: Option<String>,
+    pub span: Span,
+}
+
+impl LambdaExpr {
+    pub fn new(params: Vec<TypedIdentifier>, body: Vec<Expr>, span: Span) -> LambdaExpr {
+        LambdaExpr {
+            params,
+            body,
+            return_type: Type::Infer,
+            captures: vec![],
+            recursive: None,
+            span,
+        }
+    }
+}
+
+// A variable that a lambda uses from around it. A `val` is copied into the
+// lambda, while a `var` is shared with it so that writes on either side are
+// seen by the other
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    pub name: String,
+    pub by_reference: bool,
+}
+
+// `enum Shape { Circle(r: float), Empty }`. A variant with fields is
+// constructed by calling it, one without by naming it
+#[derive(Debug, Clone)]
+pub struct EnumDef {
+    pub name: Identifier,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+impl EnumDef {
+    pub fn new(name: Identifier, variants: Vec<Variant>, span: Span) -> EnumDef {
+        EnumDef {
+            name,
+            variants,
+            span,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: Identifier,
+    pub fields: Vec<TypedIdentifier>,
+    pub span: Span,
+}
+
+impl Variant {
+    pub fn new(name: Identifier, fields: Vec<TypedIdentifier>, span: Span) -> Variant {
+        Variant { name, fields, span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Int,
     Float,
     String,
     Bool,
+    // The ints walked by `start..stop step n`
+    Range,
     List(Box<Type>),
-    Tuple(Box<Type>),
+    Tuple(Vec<Type>),
     // type of parameters, return type
     FunctionType(Vec<Type>, Box<Option<Type>>),
+    // A struct or an enum, by the name it was declared with
+    Named(String),
     None,
+    // Not written in the source; filled in by the type checker
+    Infer,
+    // Unknown type introduced during inference. Variables left in a
+    // generic function's signature become template parameters
+    Var(u32),
 }
 
-trait ExprNode {
-    fn get_type(&self) -> Type;
+// Types print the way they are written in BDL source
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::String => write!(f, "string"),
+            Type::Bool => write!(f, "bool"),
+            Type::Range => write!(f, "range"),
+            Type::List(t) => write!(f, "list<{}>", t),
+            Type::Tuple(ts) => {
+                let ts = ts.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+                write!(f, "tuple<{}>", ts.join(", "))
+            }
+            Type::FunctionType(args, ret) => {
+                let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+                match ret.as_ref() {
+                    Some(ret) => write!(f, "({}) -> {}", args.join(", "), ret),
+                    None => write!(f, "({}) -> ?", args.join(", ")),
+                }
+            }
+            Type::Named(name) => write!(f, "{name}"),
+            Type::None => write!(f, "none"),
+            Type::Infer | Type::Var(_) => write!(f, "_"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct TypedIdentifier {
     pub value: Identifier,
     pub associated_type: Type,
+    pub span: Span,
 }
 
 impl TypedIdentifier {
-    pub fn new(value: Identifier, associated_type: Type) -> TypedIdentifier {
+    pub fn new(value: Identifier, associated_type: Type, span: Span) -> TypedIdentifier {
         TypedIdentifier {
             value,
             associated_type,
+            span,
         }
     }
 }
@@ -47,55 +195,60 @@ impl TypedIdentifier {
 #[derive(Debug, Clone)]
 pub struct IntegerLiteral {
     pub value: i128,
+    pub span: Span,
 }
 
 impl IntegerLiteral {
-    pub fn new(value: i128) -> IntegerLiteral {
-        IntegerLiteral { value }
+    pub fn new(value: i128, span: Span) -> IntegerLiteral {
+        IntegerLiteral { value, span }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct StringLiteral {
     pub value: String,
+    pub span: Span,
 }
 
 impl StringLiteral {
-    pub fn new(value: String) -> StringLiteral {
-        StringLiteral { value }
+    pub fn new(value: String, span: Span) -> StringLiteral {
+        StringLiteral { value, span }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct FloatLiteral {
     pub value: f64,
+    pub span: Span,
 }
 
 impl FloatLiteral {
-    pub fn new(value: f64) -> FloatLiteral {
-        FloatLiteral { value }
+    pub fn new(value: f64, span: Span) -> FloatLiteral {
+        FloatLiteral { value, span }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct BooleanLiteral {
     pub value: bool,
+    pub span: Span,
 }
 
 impl BooleanLiteral {
-    pub fn new(value: bool) -> BooleanLiteral {
-        BooleanLiteral { value }
+    pub fn new(value: bool, span: Span) -> BooleanLiteral {
+        BooleanLiteral { value, span }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Identifier {
     pub value: String,
+    pub span: Span,
 }
 
 impl Identifier {
-    pub fn new(value: String) -> Identifier {
-        Identifier { value }
+    pub fn new(value: String, span: Span) -> Identifier {
+        Identifier { value, span }
     }
 }
 
@@ -104,29 +257,75 @@ pub struct AssignmentExpr {
     pub target: TypedIdentifier,
     pub value: Box<Expr>,
     pub const_var: bool,
+    // Set by the type checker for a `var` that a lambda shares, which must
+    // live for as long as the lambda does
+    pub shared: bool,
+    pub span: Span,
 }
 
 impl AssignmentExpr {
-    pub fn new(target: TypedIdentifier, value: Expr, const_var: bool) -> AssignmentExpr {
+    pub fn new(
+        target: TypedIdentifier,
+        value: Expr,
+        const_var: bool,
+        span: Span,
+    ) -> AssignmentExpr {
         AssignmentExpr {
             target,
             value: Box::new(value),
             const_var,
+            shared: false,
+            span,
+        }
+    }
+}
+
+// `val (a, b) = value`, binding each element of a tuple
+#[derive(Debug, Clone)]
+pub struct DestructureExpr {
+    pub targets: Vec<TypedIdentifier>,
+    pub value: Box<Expr>,
+    pub const_var: bool,
+    // As for `AssignmentExpr`, when a lambda shares any of the targets
+    pub shared: bool,
+    pub span: Span,
+}
+
+impl DestructureExpr {
+    pub fn new(
+        targets: Vec<TypedIdentifier>,
+        value: Expr,
+        const_var: bool,
+        span: Span,
+    ) -> DestructureExpr {
+        DestructureExpr {
+            targets,
+            value: Box::new(value),
+            const_var,
+            shared: false,
+            span,
         }
     }
 }
 
+// `target = value`, where the target is a variable or a field or element
+// of one. `op` is `=`, or `+=`, `-=`, `*=` or `/=` to combine the value
+// with what the target held
 #[derive(Debug, Clone)]
 pub struct ReassignmentExpr {
-    pub target: Identifier,
+    pub target: Box<Expr>,
+    pub op: String,
     pub value: Box<Expr>,
+    pub span: Span,
 }
 
 impl ReassignmentExpr {
-    pub fn new(target: Identifier, value: Expr) -> ReassignmentExpr {
+    pub fn new(target: Expr, op: String, value: Expr, span: Span) -> ReassignmentExpr {
         ReassignmentExpr {
-            target,
+            target: Box::new(target),
+            op,
             value: Box::new(value),
+            span,
         }
     }
 }
@@ -135,31 +334,46 @@ impl ReassignmentExpr {
 pub struct MethodCallExpr {
     pub method_name: Identifier,
     pub args: Vec<Expr>,
+    // Set by the type checker when the call goes to a built-in function
+    // rather than one the program declares
+    pub builtin: bool,
+    pub span: Span,
 }
 
 impl MethodCallExpr {
-    pub fn new(method_name: Identifier, args: Vec<Expr>) -> MethodCallExpr {
-        MethodCallExpr { method_name, args }
+    pub fn new(method_name: Identifier, args: Vec<Expr>, span: Span) -> MethodCallExpr {
+        MethodCallExpr {
+            method_name,
+            args,
+            builtin: false,
+            span,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct PrintExpr {
     pub arg: Box<Expr>,
+    pub span: Span,
 }
 
 impl PrintExpr {
-    pub fn new(arg: Expr) -> PrintExpr {
-        PrintExpr { arg: Box::new(arg) }
+    pub fn new(arg: Expr, span: Span) -> PrintExpr {
+        PrintExpr {
+            arg: Box::new(arg),
+            span,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct NoneExpr;
+pub struct NoneExpr {
+    pub span: Span,
+}
 
 impl NoneExpr {
-    pub fn new() -> NoneExpr {
-        NoneExpr
+    pub fn new(span: Span) -> NoneExpr {
+        NoneExpr { span }
     }
 }
 
@@ -168,14 +382,25 @@ pub struct IfExpr {
     pub condition: Box<Expr>,
     pub then_block: Vec<Expr>,
     pub else_block: Option<Vec<Expr>>,
+    // The type of the value of both branches, filled in by the type checker.
+    // `None` without an `else`, or when they disagree
+    pub ty: Type,
+    pub span: Span,
 }
 
 impl IfExpr {
-    pub fn new(condition: Expr, then_block: Vec<Expr>, else_block: Option<Vec<Expr>>) -> IfExpr {
+    pub fn new(
+        condition: Expr,
+        then_block: Vec<Expr>,
+        else_block: Option<Vec<Expr>>,
+        span: Span,
+    ) -> IfExpr {
         IfExpr {
             condition: Box::new(condition),
             then_block,
             else_block,
+            ty: Type::Infer,
+            span,
         }
     }
 }
@@ -184,13 +409,132 @@ impl IfExpr {
 pub struct RepExpr {
     pub num_iterations: Box<Expr>,
     pub body: Vec<Expr>,
+    pub label: Option<Identifier>,
+    pub span: Span,
 }
 
 impl RepExpr {
-    pub fn new(num_iterations: Expr) -> RepExpr {
+    pub fn new(
+        num_iterations: Expr,
+        body: Vec<Expr>,
+        label: Option<Identifier>,
+        span: Span,
+    ) -> RepExpr {
         RepExpr {
             num_iterations: Box::new(num_iterations),
-            body: vec![],
+            body,
+            label,
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WhileExpr {
+    pub condition: Box<Expr>,
+    pub body: Vec<Expr>,
+    pub label: Option<Identifier>,
+    pub span: Span,
+}
+
+impl WhileExpr {
+    pub fn new(
+        condition: Expr,
+        body: Vec<Expr>,
+        label: Option<Identifier>,
+        span: Span,
+    ) -> WhileExpr {
+        WhileExpr {
+            condition: Box::new(condition),
+            body,
+            label,
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ForExpr {
+    // More than one target destructures each element
+    pub targets: Vec<TypedIdentifier>,
+    pub iterable: Box<Expr>,
+    pub body: Vec<Expr>,
+    pub label: Option<Identifier>,
+    pub span: Span,
+}
+
+impl ForExpr {
+    pub fn new(
+        targets: Vec<TypedIdentifier>,
+        iterable: Expr,
+        body: Vec<Expr>,
+        label: Option<Identifier>,
+        span: Span,
+    ) -> ForExpr {
+        ForExpr {
+            targets,
+            iterable: Box::new(iterable),
+            body,
+            label,
+            span,
+        }
+    }
+}
+
+// `start..stop`, or `start..stop step n`
+#[derive(Debug, Clone)]
+pub struct RangeExpr {
+    pub start: Box<Expr>,
+    pub stop: Box<Expr>,
+    pub step: Option<Box<Expr>>,
+    pub span: Span,
+}
+
+impl RangeExpr {
+    pub fn new(start: Expr, stop: Expr, step: Option<Expr>, span: Span) -> RangeExpr {
+        RangeExpr {
+            start: Box::new(start),
+            stop: Box::new(stop),
+            step: step.map(Box::new),
+            span,
+        }
+    }
+}
+
+// `break` or `continue`, which leave the innermost loop or the one named by
+// `label`
+#[derive(Debug, Clone)]
+pub struct BreakExpr {
+    pub label: Option<Identifier>,
+    // Where the loop it leaves starts, which the type checker fills in
+    pub target: usize,
+    pub span: Span,
+}
+
+impl BreakExpr {
+    pub fn new(label: Option<Identifier>, span: Span) -> BreakExpr {
+        BreakExpr {
+            label,
+            target: 0,
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContinueExpr {
+    pub label: Option<Identifier>,
+    // As for `BreakExpr`
+    pub target: usize,
+    pub span: Span,
+}
+
+impl ContinueExpr {
+    pub fn new(label: Option<Identifier>, span: Span) -> ContinueExpr {
+        ContinueExpr {
+            label,
+            target: 0,
+            span,
         }
     }
 }
@@ -198,12 +542,14 @@ impl RepExpr {
 #[derive(Debug, Clone)]
 pub struct ReturnExpr {
     pub value: Box<Expr>,
+    pub span: Span,
 }
 
 impl ReturnExpr {
-    pub fn new(value: Expr) -> ReturnExpr {
+    pub fn new(value: Expr, span: Span) -> ReturnExpr {
         ReturnExpr {
             value: Box::new(value),
+            span,
         }
     }
 }
@@ -211,11 +557,150 @@ impl ReturnExpr {
 #[derive(Debug, Clone)]
 pub struct ListExpr {
     pub elems: Vec<Expr>,
+    // The type of the list, filled in by the type checker
+    pub ty: Type,
+    pub span: Span,
 }
 
 impl ListExpr {
-    pub fn new(elems: Vec<Expr>) -> ListExpr {
-        ListExpr { elems }
+    pub fn new(elems: Vec<Expr>, span: Span) -> ListExpr {
+        ListExpr {
+            elems,
+            ty: Type::Infer,
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TupleExpr {
+    pub elems: Vec<Expr>,
+    pub span: Span,
+}
+
+impl TupleExpr {
+    pub fn new(elems: Vec<Expr>, span: Span) -> TupleExpr {
+        TupleExpr { elems, span }
+    }
+}
+
+// `object.field`, where the field of a tuple is its index
+#[derive(Debug, Clone)]
+pub struct FieldAccessExpr {
+    pub object: Box<Expr>,
+    pub field: String,
+    pub span: Span,
+}
+
+impl FieldAccessExpr {
+    pub fn new(object: Expr, field: String, span: Span) -> FieldAccessExpr {
+        FieldAccessExpr {
+            object: Box::new(object),
+            field,
+            span,
+        }
+    }
+}
+
+// `list[index]`
+#[derive(Debug, Clone)]
+pub struct IndexExpr {
+    pub object: Box<Expr>,
+    pub index: Box<Expr>,
+    pub span: Span,
+}
+
+impl IndexExpr {
+    pub fn new(object: Expr, index: Expr, span: Span) -> IndexExpr {
+        IndexExpr {
+            object: Box::new(object),
+            index: Box::new(index),
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    // `_`, matching anything
+    Wildcard(Span),
+    // An int, float, bool or string literal, compared with `==`
+    Literal(Expr),
+    // Matches anything, naming it for the guard and body
+    Binding(TypedIdentifier),
+    // `Circle(r)`, or a variant without fields on its own
+    Constructor(ConstructorPattern),
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Wildcard(span) => *span,
+            Pattern::Literal(e) => e.span(),
+            Pattern::Binding(b) => b.span,
+            Pattern::Constructor(c) => c.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConstructorPattern {
+    pub name: Identifier,
+    pub args: Vec<Pattern>,
+    // The enum declaring the variant, filled in by the type checker
+    pub enum_name: String,
+    pub span: Span,
+}
+
+impl ConstructorPattern {
+    pub fn new(name: Identifier, args: Vec<Pattern>, span: Span) -> ConstructorPattern {
+        ConstructorPattern {
+            name,
+            args,
+            enum_name: String::new(),
+            span,
+        }
+    }
+}
+
+// `pattern if guard => body`
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Vec<Expr>,
+    pub span: Span,
+}
+
+impl MatchArm {
+    pub fn new(pattern: Pattern, guard: Option<Expr>, body: Vec<Expr>, span: Span) -> MatchArm {
+        MatchArm {
+            pattern,
+            guard,
+            body,
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchExpr {
+    pub scrutinee: Box<Expr>,
+    pub arms: Vec<MatchArm>,
+    // The type of the value of every arm, filled in by the type checker.
+    // `None` when they disagree
+    pub ty: Type,
+    pub span: Span,
+}
+
+impl MatchExpr {
+    pub fn new(scrutinee: Expr, arms: Vec<MatchArm>, span: Span) -> MatchExpr {
+        MatchExpr {
+            scrutinee: Box::new(scrutinee),
+            arms,
+            ty: Type::Infer,
+            span,
+        }
     }
 }
 
@@ -224,14 +709,16 @@ pub struct BinOpExpr {
     pub left: Box<Expr>,
     pub op: String,
     pub right: Box<Expr>,
+    pub span: Span,
 }
 
 impl BinOpExpr {
-    pub fn new(left: Expr, op: String, right: Expr) -> BinOpExpr {
+    pub fn new(left: Expr, op: String, right: Expr, span: Span) -> BinOpExpr {
         BinOpExpr {
             left: Box::new(left),
             op,
             right: Box::new(right),
+            span,
         }
     }
 }
@@ -240,13 +727,15 @@ impl BinOpExpr {
 pub struct UnOpExpr {
     pub op: String,
     pub arg: Box<Expr>,
+    pub span: Span,
 }
 
 impl UnOpExpr {
-    pub fn new(op: String, arg: Expr) -> UnOpExpr {
+    pub fn new(op: String, arg: Expr, span: Span) -> UnOpExpr {
         UnOpExpr {
             op,
             arg: Box::new(arg),
+            span,
         }
     }
 }
@@ -259,19 +748,34 @@ pub enum Expr {
     String(StringLiteral),
     Identifier(Identifier),
     AssignmentExpr(AssignmentExpr),
+    DestructureExpr(DestructureExpr),
     ReassignmentExpr(ReassignmentExpr),
     MethodCallExpr(MethodCallExpr),
     PrintExpr(PrintExpr),
     IfExpr(IfExpr),
     RepExpr(RepExpr),
+    WhileExpr(WhileExpr),
+    ForExpr(ForExpr),
+    BreakExpr(BreakExpr),
+    ContinueExpr(ContinueExpr),
+    MatchExpr(MatchExpr),
     ListExpr(ListExpr),
+    TupleExpr(TupleExpr),
+    RangeExpr(RangeExpr),
+    FieldAccessExpr(FieldAccessExpr),
+    IndexExpr(IndexExpr),
     BinOp(BinOpExpr),
     UnOp(UnOpExpr),
     FunctionDef(FunctionDef),
+    LambdaExpr(LambdaExpr),
+    StructDef(StructDef),
+    EnumDef(EnumDef),
     NoneExpr(NoneExpr),
     ReturnExpr(ReturnExpr),
 }
 
+// The accessors are named after the variants they unwrap
+#[allow(non_snake_case)]
 impl Expr {
     pub fn Integer(self) -> Option<IntegerLiteral> {
         if let Expr::Integer(i) = self {
@@ -321,6 +825,14 @@ impl Expr {
         }
     }
 
+    pub fn DestructureExpr(self) -> Option<DestructureExpr> {
+        if let Expr::DestructureExpr(d) = self {
+            Some(d)
+        } else {
+            None
+        }
+    }
+
     pub fn ReassignmentExpr(self) -> Option<ReassignmentExpr> {
         if let Expr::ReassignmentExpr(r) = self {
             Some(r)
@@ -361,6 +873,78 @@ impl Expr {
         }
     }
 
+    pub fn WhileExpr(self) -> Option<WhileExpr> {
+        if let Expr::WhileExpr(w) = self {
+            Some(w)
+        } else {
+            None
+        }
+    }
+
+    pub fn ForExpr(self) -> Option<ForExpr> {
+        if let Expr::ForExpr(f) = self {
+            Some(f)
+        } else {
+            None
+        }
+    }
+
+    pub fn RangeExpr(self) -> Option<RangeExpr> {
+        if let Expr::RangeExpr(r) = self {
+            Some(r)
+        } else {
+            None
+        }
+    }
+
+    pub fn BreakExpr(self) -> Option<BreakExpr> {
+        if let Expr::BreakExpr(b) = self {
+            Some(b)
+        } else {
+            None
+        }
+    }
+
+    pub fn ContinueExpr(self) -> Option<ContinueExpr> {
+        if let Expr::ContinueExpr(c) = self {
+            Some(c)
+        } else {
+            None
+        }
+    }
+
+    pub fn MatchExpr(self) -> Option<MatchExpr> {
+        if let Expr::MatchExpr(m) = self {
+            Some(m)
+        } else {
+            None
+        }
+    }
+
+    pub fn TupleExpr(self) -> Option<TupleExpr> {
+        if let Expr::TupleExpr(t) = self {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    pub fn FieldAccessExpr(self) -> Option<FieldAccessExpr> {
+        if let Expr::FieldAccessExpr(f) = self {
+            Some(f)
+        } else {
+            None
+        }
+    }
+
+    pub fn IndexExpr(self) -> Option<IndexExpr> {
+        if let Expr::IndexExpr(i) = self {
+            Some(i)
+        } else {
+            None
+        }
+    }
+
     pub fn BinOp(self) -> Option<BinOpExpr> {
         if let Expr::BinOp(b) = self {
             Some(b)
@@ -385,6 +969,30 @@ impl Expr {
         }
     }
 
+    pub fn LambdaExpr(self) -> Option<LambdaExpr> {
+        if let Expr::LambdaExpr(l) = self {
+            Some(l)
+        } else {
+            None
+        }
+    }
+
+    pub fn StructDef(self) -> Option<StructDef> {
+        if let Expr::StructDef(s) = self {
+            Some(s)
+        } else {
+            None
+        }
+    }
+
+    pub fn EnumDef(self) -> Option<EnumDef> {
+        if let Expr::EnumDef(e) = self {
+            Some(e)
+        } else {
+            None
+        }
+    }
+
     pub fn NoneExpr(self) -> Option<NoneExpr> {
         if let Expr::NoneExpr(n) = self {
             Some(n)
@@ -395,25 +1003,165 @@ impl Expr {
 }
 
 impl Expr {
-    pub fn get_type(&self) -> Type {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Integer(e) => e.span,
+            Expr::Float(e) => e.span,
+            Expr::Boolean(e) => e.span,
+            Expr::String(e) => e.span,
+            Expr::Identifier(e) => e.span,
+            Expr::AssignmentExpr(e) => e.span,
+            Expr::DestructureExpr(e) => e.span,
+            Expr::ReassignmentExpr(e) => e.span,
+            Expr::MethodCallExpr(e) => e.span,
+            Expr::PrintExpr(e) => e.span,
+            Expr::IfExpr(e) => e.span,
+            Expr::RepExpr(e) => e.span,
+            Expr::WhileExpr(e) => e.span,
+            Expr::ForExpr(e) => e.span,
+            Expr::RangeExpr(e) => e.span,
+            Expr::BreakExpr(e) => e.span,
+            Expr::ContinueExpr(e) => e.span,
+            Expr::MatchExpr(e) => e.span,
+            Expr::ListExpr(e) => e.span,
+            Expr::TupleExpr(e) => e.span,
+            Expr::FieldAccessExpr(e) => e.span,
+            Expr::IndexExpr(e) => e.span,
+            Expr::BinOp(e) => e.span,
+            Expr::UnOp(e) => e.span,
+            Expr::FunctionDef(e) => e.span,
+            Expr::LambdaExpr(e) => e.span,
+            Expr::StructDef(e) => e.span,
+            Expr::EnumDef(e) => e.span,
+            Expr::NoneExpr(e) => e.span,
+            Expr::ReturnExpr(e) => e.span,
+        }
+    }
+
+    // Direct subexpressions, in source order
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Integer(_)
+            | Expr::Float(_)
+            | Expr::Boolean(_)
+            | Expr::String(_)
+            | Expr::Identifier(_)
+            | Expr::StructDef(_)
+            | Expr::EnumDef(_)
+            | Expr::BreakExpr(_)
+            | Expr::ContinueExpr(_)
+            | Expr::NoneExpr(_) => vec![],
+            Expr::AssignmentExpr(e) => vec![&e.value],
+            Expr::DestructureExpr(e) => vec![&e.value],
+            Expr::ReassignmentExpr(e) => vec![&e.target, &e.value],
+            Expr::MethodCallExpr(e) => e.args.iter().collect(),
+            Expr::PrintExpr(e) => vec![&e.arg],
+            Expr::IfExpr(e) => {
+                let mut children = vec![e.condition.as_ref()];
+                children.extend(&e.then_block);
+                children.extend(e.else_block.iter().flatten());
+                children
+            }
+            Expr::RepExpr(e) => {
+                let mut children = vec![e.num_iterations.as_ref()];
+                children.extend(&e.body);
+                children
+            }
+            Expr::WhileExpr(e) => {
+                let mut children = vec![e.condition.as_ref()];
+                children.extend(&e.body);
+                children
+            }
+            Expr::ForExpr(e) => {
+                let mut children = vec![e.iterable.as_ref()];
+                children.extend(&e.body);
+                children
+            }
+            Expr::RangeExpr(e) => {
+                let mut children = vec![e.start.as_ref(), e.stop.as_ref()];
+                children.extend(e.step.as_deref());
+                children
+            }
+            Expr::MatchExpr(e) => {
+                let mut children = vec![e.scrutinee.as_ref()];
+                for arm in &e.arms {
+                    children.extend(&arm.guard);
+                    children.extend(&arm.body);
+                }
+                children
+            }
+            Expr::ListExpr(e) => e.elems.iter().collect(),
+            Expr::TupleExpr(e) => e.elems.iter().collect(),
+            Expr::FieldAccessExpr(e) => vec![&e.object],
+            Expr::IndexExpr(e) => vec![&e.object, &e.index],
+            Expr::BinOp(e) => vec![&e.left, &e.right],
+            Expr::UnOp(e) => vec![&e.arg],
+            Expr::FunctionDef(e) => e.body.iter().collect(),
+            Expr::LambdaExpr(e) => e.body.iter().collect(),
+            Expr::ReturnExpr(e) => vec![&e.value],
+        }
+    }
+
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
         match self {
-            Expr::Integer(_) => Type::Int,
-            Expr::Float(_) => Type::Float,
-            Expr::String(_) => Type::String,
-            Expr::Boolean(_) => Type::Bool,
-            Expr::Identifier(_) => todo!(),
-            Expr::ReturnExpr(_) => todo!(),
-            Expr::AssignmentExpr(_) => todo!(),
-            Expr::ReassignmentExpr(_) => todo!(),
-            Expr::MethodCallExpr(_) => todo!(),
-            Expr::PrintExpr(_) => todo!(),
-            Expr::IfExpr(_) => todo!(),
-            Expr::RepExpr(_) => todo!(),
-            Expr::ListExpr(_) => todo!(),
-            Expr::BinOp(_) => todo!(),
-            Expr::UnOp(expr) => expr.arg.get_type(),
-            Expr::FunctionDef(fd) => todo!(),
-            Expr::NoneExpr(_) => Type::None,
+            Expr::Integer(_)
+            | Expr::Float(_)
+            | Expr::Boolean(_)
+            | Expr::String(_)
+            | Expr::Identifier(_)
+            | Expr::StructDef(_)
+            | Expr::EnumDef(_)
+            | Expr::BreakExpr(_)
+            | Expr::ContinueExpr(_)
+            | Expr::NoneExpr(_) => vec![],
+            Expr::AssignmentExpr(e) => vec![&mut e.value],
+            Expr::DestructureExpr(e) => vec![&mut e.value],
+            Expr::ReassignmentExpr(e) => vec![&mut e.target, &mut e.value],
+            Expr::MethodCallExpr(e) => e.args.iter_mut().collect(),
+            Expr::PrintExpr(e) => vec![&mut e.arg],
+            Expr::IfExpr(e) => {
+                let mut children = vec![e.condition.as_mut()];
+                children.extend(&mut e.then_block);
+                children.extend(e.else_block.iter_mut().flatten());
+                children
+            }
+            Expr::RepExpr(e) => {
+                let mut children = vec![e.num_iterations.as_mut()];
+                children.extend(&mut e.body);
+                children
+            }
+            Expr::WhileExpr(e) => {
+                let mut children = vec![e.condition.as_mut()];
+                children.extend(&mut e.body);
+                children
+            }
+            Expr::ForExpr(e) => {
+                let mut children = vec![e.iterable.as_mut()];
+                children.extend(&mut e.body);
+                children
+            }
+            Expr::RangeExpr(e) => {
+                let mut children = vec![e.start.as_mut(), e.stop.as_mut()];
+                children.extend(e.step.as_deref_mut());
+                children
+            }
+            Expr::MatchExpr(e) => {
+                let mut children = vec![e.scrutinee.as_mut()];
+                for arm in &mut e.arms {
+                    children.extend(&mut arm.guard);
+                    children.extend(&mut arm.body);
+                }
+                children
+            }
+            Expr::ListExpr(e) => e.elems.iter_mut().collect(),
+            Expr::TupleExpr(e) => e.elems.iter_mut().collect(),
+            Expr::FieldAccessExpr(e) => vec![&mut e.object],
+            Expr::IndexExpr(e) => vec![&mut e.object, &mut e.index],
+            Expr::BinOp(e) => vec![&mut e.left, &mut e.right],
+            Expr::UnOp(e) => vec![&mut e.arg],
+            Expr::FunctionDef(e) => e.body.iter_mut().collect(),
+            Expr::LambdaExpr(e) => e.body.iter_mut().collect(),
+            Expr::ReturnExpr(e) => vec![&mut e.value],
         }
     }
 }
@@ -427,21 +1175,9 @@ pub enum AstNode {
     VecExpr(Vec<Expr>),
 }
 
+// The accessors are named after the variants they unwrap
+#[allow(non_snake_case)]
 impl AstNode {
-    pub fn get_type(&self) -> Type {
-        match self {
-            AstNode::Program(program) => program
-                .expressions
-                .last()
-                .unwrap_or(&Expr::NoneExpr(NoneExpr))
-                .get_type(),
-            AstNode::Expr(expr) => expr.get_type(),
-            AstNode::TypedIdentifier(identifier) => identifier.associated_type.clone(),
-            AstNode::Type(t) => t.clone(),
-            AstNode::VecExpr(exprs) => todo!(),
-        }
-    }
-
     pub fn Expr(self) -> Option<Expr> {
         if let AstNode::Expr(e) = self {
             Some(e)
@@ -0,0 +1,67 @@
+use crate::ast::Span;
+use thiserror::Error;
+
+// Name used for sources that did not come from a file (tests, the web demo)
+pub const DEFAULT_FILE_NAME: &str = "<input>";
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{file}:{}:{}: error: {message}\n{snippet}", .span.line, .span.col)]
+pub struct ParseError {
+    pub file: String,
+    pub span: Span,
+    pub message: String,
+    // Human readable names of the tokens the parser would have accepted at `span`
+    pub expected: Vec<String>,
+    pub snippet: String,
+}
+
+impl ParseError {
+    pub fn new(source: &str, span: Span, message: impl Into<String>) -> ParseError {
+        ParseError {
+            file: DEFAULT_FILE_NAME.to_string(),
+            span,
+            message: message.into(),
+            expected: vec![],
+            snippet: render_snippet(source, &span),
+        }
+    }
+
+    pub fn with_expected(mut self, expected: Vec<String>) -> ParseError {
+        self.expected = expected;
+        self
+    }
+
+    pub fn with_file(mut self, file: &str) -> ParseError {
+        self.file = file.to_string();
+        self
+    }
+}
+
+// Renders the source line containing `span` with a caret underline, e.g.
+//
+//   |
+// 3 | val z: int = x +
+//   |                 ^
+pub fn render_snippet(source: &str, span: &Span) -> String {
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let line_text = &source[line_start..line_end];
+
+    // Keep tabs so the caret lines up with the source line in the terminal
+    let padding = source[line_start..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    let underline_end = span.end.clamp(start, line_end);
+    let width = source[start..underline_end].chars().count().max(1);
+
+    let line_number = span.line.to_string();
+    let gutter = " ".repeat(line_number.len());
+    format!(
+        "{gutter} |\n{line_number} | {line_text}\n{gutter} | {padding}{}",
+        "^".repeat(width)
+    )
+}
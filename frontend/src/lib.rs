@@ -1,2 +1,4 @@
 pub mod ast;
-pub mod parser;
\ No newline at end of file
+pub mod error;
+pub mod parser;
+pub mod typeck;
@@ -1,20 +1,39 @@
 pub mod ast;
+pub mod error;
 pub mod parser;
 #[cfg(test)]
 mod parser_test;
+pub mod typeck;
+#[cfg(test)]
+mod typeck_test;
 
 use std::env;
 use std::fs;
 
 fn main() {
     let file = env::args().nth(1).expect("No file provided");
-    let mut src = fs::read_to_string(file).expect("Failed to read file");
+    let mut src = fs::read_to_string(&file).expect("Failed to read file");
 
     if !src.ends_with('\n') {
         src.push('\n');
     }
 
-    let prog = parser::parse_program(&src).expect("Failed to parse program");
+    let mut prog = match parser::parse_source(&file, &src) {
+        Ok(prog) => prog,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let ast::AstNode::Program(program) = prog.as_mut() {
+        if let Err(errors) = typeck::check_program(program) {
+            for e in errors {
+                eprintln!("{}", e.render(&file, &src));
+            }
+            std::process::exit(1);
+        }
+    }
 
     println!("{:?}", prog);
 }
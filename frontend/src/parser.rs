@@ -2,7 +2,12 @@ use pest_derive::Parser;
 
 use crate::ast;
 use crate::ast::AstNode;
+use crate::error::ParseError;
+use pest::error::{ErrorVariant, InputLocation, LineColLocation};
+use pest::pratt_parser::{Assoc, Op, PrattParser};
 use pest::Parser;
+use pest::Position;
+use std::sync::OnceLock;
 
 #[derive(Parser)]
 #[grammar = "src/parser.pest"]
@@ -10,182 +15,613 @@ pub struct BdlParser;
 
 // The Rule enum is automatically generated by pest_derive
 
+use crate::ast::BinOpExpr;
 use crate::ast::Expr;
 use crate::ast::IfExpr;
 use crate::ast::IntegerLiteral;
 use crate::ast::ListExpr;
 use crate::ast::PrintExpr;
 use crate::ast::Program;
-use crate::ast::StringLiteral;
+use crate::ast::RepExpr;
+use crate::ast::Span;
 use crate::ast::TypedIdentifier;
+use crate::ast::UnOpExpr;
 use pest::iterators::Pair;
 
-fn build_ast_from_expr(pair: Pair<Rule>) -> Option<AstNode> {
+type ParseResult<T> = Result<T, Box<ParseError>>;
+
+// Binary operators from loosest to tightest binding, all left-associative,
+// with the unary operators binding tighter than any of them and field
+// access and indexing tightest of all. Ranges bind loosest, so that
+// `0..n + 1 step 2` is `(0..(n + 1)) step 2`
+fn pratt_parser() -> &'static PrattParser<Rule> {
+    static PRATT_PARSER: OnceLock<PrattParser<Rule>> = OnceLock::new();
+    PRATT_PARSER.get_or_init(|| {
+        PrattParser::new()
+            .op(Op::infix(Rule::op_step, Assoc::Left))
+            .op(Op::infix(Rule::op_range, Assoc::Left))
+            .op(Op::infix(Rule::op_or, Assoc::Left))
+            .op(Op::infix(Rule::op_and, Assoc::Left))
+            .op(Op::infix(Rule::op_eq, Assoc::Left)
+                | Op::infix(Rule::op_ne, Assoc::Left)
+                | Op::infix(Rule::op_lt, Assoc::Left)
+                | Op::infix(Rule::op_gt, Assoc::Left)
+                | Op::infix(Rule::op_le, Assoc::Left)
+                | Op::infix(Rule::op_ge, Assoc::Left))
+            .op(Op::infix(Rule::op_add, Assoc::Left) | Op::infix(Rule::op_sub, Assoc::Left))
+            .op(Op::infix(Rule::op_mul, Assoc::Left) | Op::infix(Rule::op_div, Assoc::Left))
+            .op(Op::prefix(Rule::un_operator))
+            .op(Op::postfix(Rule::field_access) | Op::postfix(Rule::index))
+    })
+}
+
+fn span_of(pair: &Pair<Rule>) -> Span {
+    let span = pair.as_span();
+    let (line, col) = span.start_pos().line_col();
+    Span::new(span.start(), span.end(), line, col)
+}
+
+fn error_at(pair: &Pair<Rule>, message: impl Into<String>) -> Box<ParseError> {
+    Box::new(ParseError::new(pair.get_input(), span_of(pair), message))
+}
+
+// Helpers for children whose node kind is fixed by the grammar
+fn build_expr(pair: Pair<Rule>) -> ParseResult<Expr> {
+    let context = pair.clone();
+    build_ast_from_expr(pair)?
+        .Expr()
+        .ok_or_else(|| error_at(&context, "expected an expression"))
+}
+
+fn build_identifier(pair: Pair<Rule>) -> ParseResult<ast::Identifier> {
+    let context = pair.clone();
+    build_expr(pair)?
+        .Identifier()
+        .ok_or_else(|| error_at(&context, "expected an identifier"))
+}
+
+fn build_type(pair: Pair<Rule>) -> ParseResult<ast::Type> {
+    let context = pair.clone();
+    build_ast_from_expr(pair)?
+        .Type()
+        .ok_or_else(|| error_at(&context, "expected a type"))
+}
+
+fn build_typed_identifier(pair: Pair<Rule>) -> ParseResult<TypedIdentifier> {
+    let context = pair.clone();
+    build_ast_from_expr(pair)?
+        .TypedIdentifier()
+        .ok_or_else(|| error_at(&context, "expected a typed identifier"))
+}
+
+// A binding target whose annotation may be left out, leaving its type to
+// the type checker
+fn build_binding(pair: Pair<Rule>) -> ParseResult<TypedIdentifier> {
+    if pair.as_rule() == Rule::identifier {
+        let id = build_identifier(pair)?;
+        let span = id.span;
+        Ok(TypedIdentifier::new(id, ast::Type::Infer, span))
+    } else {
+        build_typed_identifier(pair)
+    }
+}
+
+fn build_block(pair: Pair<Rule>) -> ParseResult<Vec<Expr>> {
+    let context = pair.clone();
+    build_ast_from_expr(pair)?
+        .VecExpr()
+        .ok_or_else(|| error_at(&context, "expected a block"))
+}
+
+fn build_variant(pair: Pair<Rule>) -> ParseResult<ast::Variant> {
+    let span = span_of(&pair);
+    let mut inner_rules = pair.into_inner();
+    let name = build_identifier(inner_rules.next().unwrap())?;
+    let fields = inner_rules
+        .map(build_typed_identifier)
+        .collect::<ParseResult<Vec<TypedIdentifier>>>()?;
+    Ok(ast::Variant::new(name, fields, span))
+}
+
+fn build_pattern(pair: Pair<Rule>) -> ParseResult<ast::Pattern> {
+    match pair.as_rule() {
+        Rule::wildcard => Ok(ast::Pattern::Wildcard(span_of(&pair))),
+        Rule::identifier => Ok(ast::Pattern::Binding(build_binding(pair)?)),
+        Rule::constructor_pattern => {
+            let span = span_of(&pair);
+            let mut inner_rules = pair.into_inner();
+            let name = build_identifier(inner_rules.next().unwrap())?;
+            let args = inner_rules
+                .map(build_pattern)
+                .collect::<ParseResult<Vec<ast::Pattern>>>()?;
+            Ok(ast::Pattern::Constructor(ast::ConstructorPattern::new(
+                name, args, span,
+            )))
+        }
+        _ => Ok(ast::Pattern::Literal(build_expr(pair)?)),
+    }
+}
+
+// The body of an arm is either a block or a single expression
+fn build_match_arm(pair: Pair<Rule>) -> ParseResult<ast::MatchArm> {
+    let span = span_of(&pair);
+    let mut inner_rules = pair.into_inner().collect::<Vec<Pair<Rule>>>();
+    let body = inner_rules.pop().unwrap();
+    let body = match body.as_rule() {
+        Rule::block => build_block(body)?,
+        _ => vec![build_expr(body)?],
+    };
+    let mut inner_rules = inner_rules.into_iter();
+    let pattern = build_pattern(inner_rules.next().unwrap())?;
+    let guard = inner_rules
+        .next()
+        .map(|guard| build_expr(guard.into_inner().next().unwrap()))
+        .transpose()?;
+    Ok(ast::MatchArm::new(pattern, guard, body, span))
+}
+
+fn build_ast_from_expr(pair: Pair<Rule>) -> ParseResult<AstNode> {
     match pair.as_rule() {
         Rule::program => {
             let nodes = pair
                 .into_inner()
-                .filter_map(|pair| build_ast_from_expr(pair)?.Expr())
-                .collect::<Vec<Expr>>();
-            Some(AstNode::Program(Program { expressions: nodes }))
+                .filter(|pair| pair.as_rule() != Rule::EOI)
+                .map(build_expr)
+                .collect::<ParseResult<Vec<Expr>>>()?;
+            Ok(AstNode::Program(Program { expressions: nodes }))
         }
-        Rule::expression => build_ast_from_expr(pair.into_inner().next()?),
         Rule::typed_identifier => {
+            let span = span_of(&pair);
             let mut inner_nodes = pair.into_inner().collect::<Vec<Pair<Rule>>>();
             assert!(inner_nodes.len() == 2);
-            let mut type_of_id = build_ast_from_expr(inner_nodes.pop()?)?.Type()?;
-            let mut id = build_ast_from_expr(inner_nodes.pop()?)?
-                .Expr()?
-                .Identifier()?;
-            Some(AstNode::TypedIdentifier(TypedIdentifier {
-                value: id,
-                associated_type: type_of_id,
-            }))
+            let type_of_id = build_type(inner_nodes.pop().unwrap())?;
+            let id = build_identifier(inner_nodes.pop().unwrap())?;
+            Ok(AstNode::TypedIdentifier(TypedIdentifier::new(
+                id, type_of_id, span,
+            )))
         }
         Rule::type_annotation => {
             let p_clone = pair.clone();
-            let mut inner = pair.into_inner();
             let type_name = p_clone.as_str().to_string();
-            if (inner.len() >= 1) {
-                let inner_type = build_ast_from_expr(inner.next()?)?.Type()?;
+            // `(int, int) -> int`, whose last type is the return type
+            if type_name.starts_with('(') {
+                let mut types = pair
+                    .into_inner()
+                    .map(build_type)
+                    .collect::<ParseResult<Vec<ast::Type>>>()?;
+                let ret = types.pop().unwrap();
+                return Ok(AstNode::Type(ast::Type::FunctionType(
+                    types,
+                    Box::new(Some(ret)),
+                )));
+            }
+            let mut inner = pair.into_inner();
+            let inner_pair = inner.next().unwrap();
+            if inner_pair.as_rule() == Rule::identifier {
+                Ok(AstNode::Type(match inner_pair.as_str() {
+                    "int" => ast::Type::Int,
+                    "float" => ast::Type::Float,
+                    "bool" => ast::Type::Bool,
+                    "range" => ast::Type::Range,
+                    "string" => ast::Type::String,
+                    "none" => ast::Type::None,
+                    name => ast::Type::Named(name.to_string()),
+                }))
+            } else {
+                let inner_type = build_type(inner_pair)?;
                 if type_name.starts_with("list") {
-                    Some(AstNode::Type(ast::Type::List(Box::new(inner_type))))
+                    Ok(AstNode::Type(ast::Type::List(Box::new(inner_type))))
                 } else if type_name.starts_with("tuple") {
-                    Some(AstNode::Type(ast::Type::Tuple(Box::new(inner_type))))
+                    let mut elems = vec![inner_type];
+                    for elem in inner {
+                        elems.push(build_type(elem)?);
+                    }
+                    Ok(AstNode::Type(ast::Type::Tuple(elems)))
                 } else {
-                    None
-                }
-            } else {
-                match type_name.as_str() {
-                    "int" => Some(AstNode::Type(ast::Type::Int)),
-                    "float" => Some(AstNode::Type(ast::Type::Float)),
-                    "string" => Some(AstNode::Type(ast::Type::String)),
-                    _ => None,
+                    Err(error_at(&p_clone, format!("unknown type `{type_name}`")))
                 }
             }
         }
-        Rule::identifier => Some(AstNode::Expr(Expr::Identifier(ast::Identifier {
-            value: pair.as_str().to_string(),
-        }))),
+        Rule::identifier => Ok(AstNode::Expr(Expr::Identifier(ast::Identifier::new(
+            pair.as_str().to_string(),
+            span_of(&pair),
+        )))),
         Rule::assignment => {
+            let span = span_of(&pair);
             let mut inner_rules = pair.into_inner().collect::<Vec<Pair<Rule>>>();
-            // typed identifier and expression
-            assert!(inner_rules.len() == 3);
+            // var/val, (typed) identifier and expression. Without var/val the
+            // binding is a val
+            assert!(inner_rules.len() == 3 || inner_rules.len() == 2);
 
-            let expr = build_ast_from_expr(inner_rules.pop()?)?.Expr();
-            let identifier = build_ast_from_expr(inner_rules.pop()?)?.TypedIdentifier();
-            let var_val = inner_rules.pop()?;
+            let expr = build_expr(inner_rules.pop().unwrap())?;
+            let identifier = build_binding(inner_rules.pop().unwrap())?;
+            let const_var = inner_rules
+                .pop()
+                .is_none_or(|var_val| var_val.as_str() == "val");
 
-            Some(AstNode::Expr(Expr::AssignmentExpr(ast::AssignmentExpr {
-                target: identifier?,
-                value: Box::new(expr?),
-                const_var: (var_val.as_str() == "val"),
-            })))
+            Ok(AstNode::Expr(Expr::AssignmentExpr(
+                ast::AssignmentExpr::new(identifier, expr, const_var, span),
+            )))
+        }
+
+        Rule::destructure => {
+            let span = span_of(&pair);
+            let mut inner_rules = pair.into_inner().collect::<Vec<Pair<Rule>>>();
+            // var/val, two or more (typed) identifiers and the expression
+            let expr = build_expr(inner_rules.pop().unwrap())?;
+            let mut inner_rules = inner_rules.into_iter();
+            let var_val = inner_rules.next().unwrap();
+            let targets = inner_rules
+                .map(build_binding)
+                .collect::<ParseResult<Vec<TypedIdentifier>>>()?;
+            Ok(AstNode::Expr(Expr::DestructureExpr(
+                ast::DestructureExpr::new(targets, expr, var_val.as_str() == "val", span),
+            )))
+        }
+
+        Rule::reassignment => {
+            let span = span_of(&pair);
+            let mut inner_rules = pair.into_inner();
+            let target = build_expr(inner_rules.next().unwrap())?;
+            let op = inner_rules.next().unwrap().as_str().to_string();
+            let value = build_expr(inner_rules.next().unwrap())?;
+            Ok(AstNode::Expr(Expr::ReassignmentExpr(
+                ast::ReassignmentExpr::new(target, op, value, span),
+            )))
+        }
+
+        Rule::struct_def => {
+            let span = span_of(&pair);
+            let mut inner_rules = pair.into_inner();
+            let name = build_identifier(inner_rules.next().unwrap())?;
+            let fields = inner_rules
+                .map(build_typed_identifier)
+                .collect::<ParseResult<Vec<TypedIdentifier>>>()?;
+            Ok(AstNode::Expr(Expr::StructDef(ast::StructDef::new(
+                name, fields, span,
+            ))))
+        }
+
+        Rule::enum_def => {
+            let span = span_of(&pair);
+            let mut inner_rules = pair.into_inner();
+            let name = build_identifier(inner_rules.next().unwrap())?;
+            let variants = inner_rules
+                .map(build_variant)
+                .collect::<ParseResult<Vec<ast::Variant>>>()?;
+            Ok(AstNode::Expr(Expr::EnumDef(ast::EnumDef::new(
+                name, variants, span,
+            ))))
         }
 
         Rule::print_expr => {
-            let expr = build_ast_from_expr(pair.into_inner().next()?)?.Expr();
-            let print_expr = PrintExpr::new(expr?);
-            Some(AstNode::Expr(Expr::PrintExpr(print_expr)))
+            let span = span_of(&pair);
+            let expr = build_expr(pair.into_inner().next().unwrap())?;
+            let print_expr = PrintExpr::new(expr, span);
+            Ok(AstNode::Expr(Expr::PrintExpr(print_expr)))
+        }
+        Rule::method_call => {
+            let span = span_of(&pair);
+            let mut inner_rules = pair.into_inner();
+            let name = build_identifier(inner_rules.next().unwrap())?;
+            let args = inner_rules
+                .map(build_expr)
+                .collect::<ParseResult<Vec<Expr>>>()?;
+            Ok(AstNode::Expr(Expr::MethodCallExpr(
+                ast::MethodCallExpr::new(name, args, span),
+            )))
         }
+        Rule::boolean => Ok(AstNode::Expr(Expr::Boolean(ast::BooleanLiteral::new(
+            pair.as_str() == "true",
+            span_of(&pair),
+        )))),
         Rule::integer => {
-            let int_value = pair.as_str().parse::<i128>().unwrap();
-            Some(AstNode::Expr(Expr::Integer(IntegerLiteral::new(int_value))))
+            let int_value = pair
+                .as_str()
+                .parse::<i128>()
+                .map_err(|_| error_at(&pair, "integer literal is out of range"))?;
+            Ok(AstNode::Expr(Expr::Integer(IntegerLiteral::new(
+                int_value,
+                span_of(&pair),
+            ))))
         }
         Rule::list_expr => {
-            let mut inner = pair.into_inner();
-            let mut elements = Vec::new();
-            while let Some(element) = inner.next() {
-                let element = build_ast_from_expr(element)?;
-                elements.push(element.Expr()?);
-            }
-            Some(AstNode::Expr(Expr::ListExpr(ListExpr { elems: elements })))
+            let span = span_of(&pair);
+            let elements = pair
+                .into_inner()
+                .map(build_expr)
+                .collect::<ParseResult<Vec<Expr>>>()?;
+            Ok(AstNode::Expr(Expr::ListExpr(ListExpr::new(elements, span))))
+        }
+        Rule::tuple_expr => {
+            let span = span_of(&pair);
+            let elements = pair
+                .into_inner()
+                .map(build_expr)
+                .collect::<ParseResult<Vec<Expr>>>()?;
+            Ok(AstNode::Expr(Expr::TupleExpr(ast::TupleExpr::new(
+                elements, span,
+            ))))
         }
         Rule::string_literal => {
             let string_value = pair.as_str().trim_matches('"').to_string();
-            Some(AstNode::Expr(Expr::String(ast::StringLiteral {
-                value: string_value,
-            })))
+            Ok(AstNode::Expr(Expr::String(ast::StringLiteral::new(
+                string_value,
+                span_of(&pair),
+            ))))
         }
         Rule::float => {
-            let float_value = pair.as_str().parse::<f64>().unwrap();
-            Some(AstNode::Expr(Expr::Float(ast::FloatLiteral {
-                value: float_value,
-            })))
+            let float_value = pair
+                .as_str()
+                .parse::<f64>()
+                .map_err(|_| error_at(&pair, "invalid float literal"))?;
+            Ok(AstNode::Expr(Expr::Float(ast::FloatLiteral::new(
+                float_value,
+                span_of(&pair),
+            ))))
         }
         Rule::function_def => {
-            let inner_rules = pair.into_inner().collect::<Vec<Pair<Rule>>>();
-            let name = build_ast_from_expr(inner_rules.get(0)?.clone())?
-                .Expr()?
-                .Identifier()?;
-            let args = inner_rules[1..inner_rules.len() - 1]
-                .iter()
-                .filter_map(|rule| build_ast_from_expr(rule.clone())?.TypedIdentifier())
-                .collect::<Vec<TypedIdentifier>>();
-            let body = build_ast_from_expr(inner_rules.last()?.clone())?.VecExpr()?;
-            Some(AstNode::Expr(Expr::FunctionDef(ast::FunctionDef {
+            let span = span_of(&pair);
+            let mut inner_rules = pair.into_inner().collect::<Vec<Pair<Rule>>>();
+            let body = build_block(inner_rules.pop().unwrap())?;
+            let return_type = match inner_rules.last() {
+                Some(last) if last.as_rule() == Rule::type_annotation => {
+                    Some(build_type(inner_rules.pop().unwrap())?)
+                }
+                _ => None,
+            };
+            let mut inner_rules = inner_rules.into_iter();
+            let name = build_identifier(inner_rules.next().unwrap())?;
+            let args = inner_rules
+                .map(build_binding)
+                .collect::<ParseResult<Vec<TypedIdentifier>>>()?;
+            Ok(AstNode::Expr(Expr::FunctionDef(ast::FunctionDef {
                 name,
                 args,
+                return_type,
                 body,
+                span,
             })))
         }
-        Rule::return_expr => {
-            let expr = build_ast_from_expr(pair.into_inner().next()?)?.Expr()?;
-            Some(AstNode::Expr(Expr::ReturnExpr(ast::ReturnExpr {
-                value: Box::new(expr),
-            })))
-        }
-        Rule::bin_op => {
+        Rule::lambda => {
+            let span = span_of(&pair);
             let mut inner_rules = pair.into_inner().collect::<Vec<Pair<Rule>>>();
-            let right = build_ast_from_expr(inner_rules.pop()?)?.Expr();
-            let op = inner_rules.pop()?.as_str().to_string();
-            let left = build_ast_from_expr(inner_rules.pop()?)?.Expr();
-            Some(AstNode::Expr(Expr::BinOp(ast::BinOpExpr {
-                left: Box::new(left?),
-                op,
-                right: Box::new(right?),
-            })))
+            let body = inner_rules.pop().unwrap();
+            let body = match body.as_rule() {
+                Rule::block => build_block(body)?,
+                _ => vec![build_expr(body)?],
+            };
+            let params = inner_rules
+                .into_iter()
+                .map(build_binding)
+                .collect::<ParseResult<Vec<TypedIdentifier>>>()?;
+            Ok(AstNode::Expr(Expr::LambdaExpr(ast::LambdaExpr::new(
+                params, body, span,
+            ))))
+        }
+        Rule::return_expr => {
+            let span = span_of(&pair);
+            let expr = build_expr(pair.into_inner().next().unwrap())?;
+            Ok(AstNode::Expr(Expr::ReturnExpr(ast::ReturnExpr::new(
+                expr, span,
+            ))))
         }
+        Rule::bin_op | Rule::un_op | Rule::access => pratt_parser()
+            .map_primary(build_expr)
+            .map_prefix(|op, arg| {
+                let arg = arg?;
+                let span = span_of(&op).to(&arg.span());
+                Ok(Expr::UnOp(UnOpExpr::new(
+                    op.as_str().to_string(),
+                    arg,
+                    span,
+                )))
+            })
+            .map_postfix(|object, postfix| {
+                let object = object?;
+                let span = object.span().to(&span_of(&postfix));
+                if postfix.as_rule() == Rule::index {
+                    let index = build_expr(postfix.into_inner().next().unwrap())?;
+                    return Ok(Expr::IndexExpr(ast::IndexExpr::new(object, index, span)));
+                }
+                let name = postfix.as_str()[1..].to_string();
+                Ok(Expr::FieldAccessExpr(ast::FieldAccessExpr::new(
+                    object, name, span,
+                )))
+            })
+            .map_infix(|left, op, right| {
+                let (left, right) = (left?, right?);
+                let span = left.span().to(&right.span());
+                let left = match (op.as_rule(), left) {
+                    (Rule::op_range, left) => {
+                        let range = ast::RangeExpr::new(left, right, None, span);
+                        return Ok(Expr::RangeExpr(range));
+                    }
+                    (Rule::op_step, Expr::RangeExpr(range)) if range.step.is_none() => {
+                        let (start, stop) = (*range.start, *range.stop);
+                        let range = ast::RangeExpr::new(start, stop, Some(right), span);
+                        return Ok(Expr::RangeExpr(range));
+                    }
+                    (Rule::op_step, _) => {
+                        return Err(error_at(&op, "`step` can only follow a range"));
+                    }
+                    (_, left) => left,
+                };
+                Ok(Expr::BinOp(BinOpExpr::new(
+                    left,
+                    op.as_str().to_string(),
+                    right,
+                    span,
+                )))
+            })
+            .parse(pair.into_inner())
+            .map(AstNode::Expr),
         Rule::block => {
             let vec_rules = pair
                 .into_inner()
-                .filter_map(|rule| build_ast_from_expr(rule)?.Expr())
-                .collect::<Vec<Expr>>();
-            Some(AstNode::VecExpr(vec_rules))
+                .map(build_expr)
+                .collect::<ParseResult<Vec<Expr>>>()?;
+            Ok(AstNode::VecExpr(vec_rules))
         }
         Rule::if_expr => {
-            let inner_rules = pair.into_inner().collect::<Vec<Pair<Rule>>>();
-            let cond = build_ast_from_expr(inner_rules.get(0)?.clone())?.Expr()?;
-            let body = build_ast_from_expr(inner_rules.get(1)?.clone())?.VecExpr()?;
+            // The span starts at `if`, after any blank lines
+            let blank = pair.as_str().len() - pair.as_str().trim_start().len();
+            let start = Position::new(pair.get_input(), pair.as_span().start() + blank).unwrap();
+            let (line, col) = start.line_col();
+            let span = Span::new(start.pos(), pair.as_span().end(), line, col);
+            let mut inner_rules = pair.into_inner();
+            let cond = build_expr(inner_rules.next().unwrap())?;
+            let body = build_block(inner_rules.next().unwrap())?;
+            let else_expr = inner_rules.next().map(build_block).transpose()?;
 
-            let else_expr = if inner_rules.len() == 3 {
-                Some(build_ast_from_expr(inner_rules.get(2)?.clone())?.VecExpr()?)
+            Ok(AstNode::Expr(Expr::IfExpr(IfExpr::new(
+                cond, body, else_expr, span,
+            ))))
+        }
+        Rule::rep_expr | Rule::while_expr => {
+            let span = span_of(&pair);
+            let rule = pair.as_rule();
+            let mut inner_rules = pair.into_inner().peekable();
+            let label = match inner_rules.next_if(|p| p.as_rule() == Rule::label) {
+                Some(label) => Some(build_identifier(label.into_inner().next().unwrap())?),
+                None => None,
+            };
+            let head = build_expr(inner_rules.next().unwrap())?;
+            let body = build_block(inner_rules.next().unwrap())?;
+            let expr = if rule == Rule::rep_expr {
+                Expr::RepExpr(RepExpr::new(head, body, label, span))
             } else {
-                None
+                Expr::WhileExpr(ast::WhileExpr::new(head, body, label, span))
             };
-
-            Some(AstNode::Expr(Expr::IfExpr(IfExpr::new(
-                cond, body, else_expr,
+            Ok(AstNode::Expr(expr))
+        }
+        Rule::for_expr => {
+            let span = span_of(&pair);
+            let mut inner_rules = pair.into_inner().collect::<Vec<Pair<Rule>>>();
+            let body = build_block(inner_rules.pop().unwrap())?;
+            let iterable = build_expr(inner_rules.pop().unwrap())?;
+            let mut inner_rules = inner_rules.into_iter().peekable();
+            let label = match inner_rules.next_if(|p| p.as_rule() == Rule::label) {
+                Some(label) => Some(build_identifier(label.into_inner().next().unwrap())?),
+                None => None,
+            };
+            let targets = inner_rules
+                .map(build_binding)
+                .collect::<ParseResult<Vec<TypedIdentifier>>>()?;
+            Ok(AstNode::Expr(Expr::ForExpr(ast::ForExpr::new(
+                targets, iterable, body, label, span,
+            ))))
+        }
+        Rule::break_expr | Rule::continue_expr => {
+            let span = span_of(&pair);
+            let rule = pair.as_rule();
+            let label = pair.into_inner().next().map(build_identifier).transpose()?;
+            Ok(AstNode::Expr(if rule == Rule::break_expr {
+                Expr::BreakExpr(ast::BreakExpr::new(label, span))
+            } else {
+                Expr::ContinueExpr(ast::ContinueExpr::new(label, span))
+            }))
+        }
+        Rule::match_expr => {
+            let span = span_of(&pair);
+            let mut inner_rules = pair.into_inner();
+            let scrutinee = build_expr(inner_rules.next().unwrap())?;
+            let arms = inner_rules
+                .map(build_match_arm)
+                .collect::<ParseResult<Vec<ast::MatchArm>>>()?;
+            Ok(AstNode::Expr(Expr::MatchExpr(ast::MatchExpr::new(
+                scrutinee, arms, span,
             ))))
         }
-        _ => None,
+        _ => Err(error_at(
+            &pair,
+            format!("{} is not supported yet", describe_rule(pair.as_rule())),
+        )),
     }
 }
 
-pub fn parse_program(input: &str) -> Result<Box<AstNode>, String> {
-    match BdlParser::parse(Rule::program, input) {
-        Ok(parsed) => {
-            for pair in parsed {
-                let node = match build_ast_from_expr(pair) {
-                    Some(n) => n,
-                    None => return Err("Failed to build AST from expression".to_string()),
-                };
-                return Ok(Box::new(node));
-            }
-        }
-        Err(e) => {
-            return Err(e.to_string());
+// Names rules the way a user would recognise them in an error message
+fn describe_rule(rule: Rule) -> String {
+    let name = match rule {
+        Rule::EOI => "end of input",
+        Rule::NEWLINE => "newline",
+        Rule::op_or => "`||`",
+        Rule::op_and => "`&&`",
+        Rule::op_eq => "`==`",
+        Rule::op_ne => "`!=`",
+        Rule::op_le => "`<=`",
+        Rule::op_ge => "`>=`",
+        Rule::op_lt => "`<`",
+        Rule::op_gt => "`>`",
+        Rule::op_add => "`+`",
+        Rule::op_sub => "`-`",
+        Rule::op_mul => "`*`",
+        Rule::op_div => "`/`",
+        Rule::op_range => "`..`",
+        Rule::op_step => "`step`",
+        Rule::bin_op => "binary operation",
+        Rule::un_op => "unary operation",
+        Rule::un_operator => "unary operator",
+        Rule::boolean => "`true` or `false`",
+        Rule::field_access => "field access",
+        Rule::index => "index",
+        Rule::assign_op => "`=`",
+        Rule::var_val => "`val` or `var`",
+        Rule::rep_expr => "rep expression",
+        Rule::match_arm => "match arm",
+        Rule::wildcard => "`_`",
+        Rule::method_call => "function call",
+        _ => return format!("{:?}", rule).replace('_', " "),
+    };
+    name.to_string()
+}
+
+fn from_pest_error(input: &str, error: pest::error::Error<Rule>) -> Box<ParseError> {
+    let (start, end) = match error.location {
+        InputLocation::Pos(pos) => (pos, pos),
+        InputLocation::Span(span) => span,
+    };
+    let (line, col) = match error.line_col {
+        LineColLocation::Pos(pos) => pos,
+        LineColLocation::Span(pos, _) => pos,
+    };
+    let span = Span::new(start, end, line, col);
+
+    let found = match input[start..].chars().next() {
+        None => "end of input".to_string(),
+        Some('\n') => "newline".to_string(),
+        Some(c) => format!("`{c}`"),
+    };
+    match error.variant {
+        ErrorVariant::ParsingError {
+            positives,
+            negatives,
+        } => {
+            let expected = positives
+                .into_iter()
+                .map(describe_rule)
+                .collect::<Vec<String>>();
+            let message = match (expected.as_slice(), negatives.as_slice()) {
+                ([], [negative, ..]) => format!("unexpected {}", describe_rule(*negative)),
+                ([], []) => format!("unexpected {found}"),
+                ([only], _) => format!("expected {only}, found {found}"),
+                (many, _) => format!("expected one of {}, found {found}", many.join(", ")),
+            };
+            Box::new(ParseError::new(input, span, message).with_expected(expected))
         }
+        ErrorVariant::CustomError { message } => Box::new(ParseError::new(input, span, message)),
     }
-    return Err("Failed to parse program".to_string());
+}
+
+pub fn parse_program(input: &str) -> ParseResult<Box<AstNode>> {
+    let mut parsed =
+        BdlParser::parse(Rule::program, input).map_err(|e| from_pest_error(input, e))?;
+    // The program rule always matches exactly one pair spanning the whole input
+    let program = parsed.next().unwrap();
+    Ok(Box::new(build_ast_from_expr(program)?))
+}
+
+// Like `parse_program`, but errors name `file_name` as their source
+pub fn parse_source(file_name: &str, input: &str) -> ParseResult<Box<AstNode>> {
+    parse_program(input).map_err(|e| Box::new(e.with_file(file_name)))
 }
@@ -1,12 +1,31 @@
 #[cfg(test)]
 mod tests {
-    use crate::parser::{BdlParser, Rule};
+    use crate::ast::{BooleanLiteral, Expr, Pattern, Type};
+    use crate::parser::{parse_program, parse_source, BdlParser, Rule};
     use pest::Parser;
 
     fn parse(input: &str, rule: Rule) -> bool {
         BdlParser::parse(rule, input).is_ok()
     }
 
+    fn parse_expr(input: &str) -> Expr {
+        let program = parse_program(&format!("{input}\n"))
+            .unwrap()
+            .Program()
+            .unwrap();
+        program.expressions.into_iter().next().unwrap()
+    }
+
+    // Renders binary expression trees as s-expressions so nesting is easy to assert on
+    fn sexpr(expr: &Expr) -> String {
+        match expr {
+            Expr::BinOp(b) => format!("({} {} {})", b.op, sexpr(&b.left), sexpr(&b.right)),
+            Expr::Identifier(i) => i.value.clone(),
+            Expr::Integer(i) => i.value.to_string(),
+            other => panic!("unexpected expression {:?}", other),
+        }
+    }
+
     #[test]
     fn test_basic_values() {
         // Integers
@@ -38,6 +57,27 @@ mod tests {
         assert!(parse("nums: list<int> = method()", Rule::assignment));
     }
 
+    #[test]
+    fn test_declarations_without_val() {
+        let assign = parse_expr("x: int = 42").AssignmentExpr().unwrap();
+        assert!(assign.const_var);
+        assert!(!parse("x = 42", Rule::assignment));
+        // A leading blank line is not part of the if
+        let if_expr = parse_expr("\n\nif x {\n}").IfExpr().unwrap();
+        assert_eq!((if_expr.span.line, if_expr.span.col), (3, 1));
+    }
+
+    #[test]
+    fn test_unannotated_assignments() {
+        assert!(parse("val x = 42", Rule::assignment));
+        assert!(parse("var xs = [1, 2]", Rule::assignment));
+        assert!(parse("var xs = []", Rule::assignment));
+
+        let assign = parse_expr("val x = 42").AssignmentExpr().unwrap();
+        assert_eq!(assign.target.value.value, "x");
+        assert_eq!(assign.target.associated_type, Type::Infer);
+    }
+
     #[test]
     fn test_function_definitions() {
         assert!(parse(
@@ -48,6 +88,210 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_return_type_annotations() {
+        assert!(parse(
+            "def add(x: int, y: int): int {\nx + y\n}",
+            Rule::function_def
+        ));
+        assert!(parse(
+            "def names(): list<string> {\n[]\n}",
+            Rule::function_def
+        ));
+
+        let add = parse_expr("def add(x: int, y): int {\nx + y\n}")
+            .FunctionDef()
+            .unwrap();
+        assert_eq!(add.return_type, Some(Type::Int));
+        assert_eq!(add.args[1].associated_type, Type::Infer);
+        let inc = parse_expr("def inc(x) {\nx + 1\n}").FunctionDef().unwrap();
+        assert_eq!(inc.return_type, None);
+    }
+
+    #[test]
+    fn test_booleans() {
+        assert!(parse("true", Rule::boolean));
+        assert!(parse("val ok: bool = false", Rule::assignment));
+        assert!(!parse("trueish", Rule::boolean));
+
+        let ok = parse_expr("val ok = !done && trueish")
+            .AssignmentExpr()
+            .unwrap();
+        let and = ok.value.BinOp().unwrap();
+        assert_eq!(and.op, "&&");
+        let not = and.left.UnOp().unwrap();
+        assert_eq!(not.op, "!");
+        assert!(matches!(*not.arg, Expr::Identifier(_)));
+        assert!(matches!(*and.right, Expr::Identifier(_)));
+
+        let typed = parse_expr("val b: bool = true").AssignmentExpr().unwrap();
+        assert_eq!(typed.target.associated_type, Type::Bool);
+        assert!(matches!(
+            *typed.value,
+            Expr::Boolean(BooleanLiteral { value: true, .. })
+        ));
+    }
+
+    #[test]
+    fn test_tuples() {
+        assert!(parse("tuple<int, string, float>", Rule::type_annotation));
+        assert!(parse("(1, \"a\", 2.5)", Rule::tuple_expr));
+        assert!(parse("(1,)", Rule::tuple_expr));
+        assert!(!parse("(1)", Rule::tuple_expr));
+
+        let typed = parse_expr("val t: tuple<int, list<string>> = (1, [])")
+            .AssignmentExpr()
+            .unwrap();
+        assert_eq!(
+            typed.target.associated_type,
+            Type::Tuple(vec![Type::Int, Type::List(Box::new(Type::String))])
+        );
+        assert_eq!(typed.value.TupleExpr().unwrap().elems.len(), 2);
+
+        let sum = parse_expr("t.0 + f().1.2").BinOp().unwrap();
+        let left = sum.left.FieldAccessExpr().unwrap();
+        assert_eq!(left.field, "0");
+        assert!(matches!(*left.object, Expr::Identifier(_)));
+        let right = sum.right.FieldAccessExpr().unwrap();
+        assert_eq!(right.field, "2");
+        let inner = right.object.FieldAccessExpr().unwrap();
+        assert_eq!(inner.field, "1");
+        assert!(matches!(*inner.object, Expr::MethodCallExpr(_)));
+
+        let destructure = parse_expr("var (a, b: int) = pair()")
+            .DestructureExpr()
+            .unwrap();
+        assert!(!destructure.const_var);
+        let targets = destructure
+            .targets
+            .iter()
+            .map(|t| (t.value.value.as_str(), t.associated_type.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(targets, vec![("a", Type::Infer), ("b", Type::Int)]);
+    }
+
+    #[test]
+    fn test_structs() {
+        assert!(parse("struct Point { x: int, y: int }", Rule::struct_def));
+        assert!(parse("struct Empty {}", Rule::struct_def));
+        assert!(parse(
+            "struct Line {\n  start: Point,\n  end: Point,\n}",
+            Rule::struct_def
+        ));
+
+        let program = parse_program(
+            "struct Pair { first: int, rest: list<Pair> }\nval p: Pair = Pair(1, [])\np.first = p.first + 1\n",
+        )
+        .unwrap()
+        .Program()
+        .unwrap();
+        let mut exprs = program.expressions.into_iter();
+        let def = exprs.next().unwrap().StructDef().unwrap();
+        assert_eq!(def.name.value, "Pair");
+        let fields = def
+            .fields
+            .iter()
+            .map(|f| (f.value.value.as_str(), f.associated_type.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                ("first", Type::Int),
+                (
+                    "rest",
+                    Type::List(Box::new(Type::Named("Pair".to_string())))
+                )
+            ]
+        );
+        let assign = exprs.next().unwrap().AssignmentExpr().unwrap();
+        assert_eq!(
+            assign.target.associated_type,
+            Type::Named("Pair".to_string())
+        );
+        assert!(matches!(*assign.value, Expr::MethodCallExpr(_)));
+        let write = exprs.next().unwrap().ReassignmentExpr().unwrap();
+        assert_eq!(write.target.FieldAccessExpr().unwrap().field, "first");
+        assert!(matches!(*write.value, Expr::BinOp(_)));
+    }
+
+    #[test]
+    fn test_enums() {
+        assert!(parse("enum Color { Red, Green, Blue }", Rule::enum_def));
+        assert!(parse("enum Never {}", Rule::enum_def));
+        assert!(parse(
+            "enum Shape {\n  Circle(r: float),\n  Rect(w: float, h: float),\n}",
+            Rule::enum_def
+        ));
+
+        let program = parse_program(
+            "enum Shape { Circle(r: float), Empty }\nval s = match Empty {\nCircle(r) if r > 1.0 => r\nEmpty => {\n0.0\n}\n_ => 1.0\n}\n",
+        )
+        .unwrap()
+        .Program()
+        .unwrap();
+        let mut exprs = program.expressions.into_iter();
+        let def = exprs.next().unwrap().EnumDef().unwrap();
+        assert_eq!(def.name.value, "Shape");
+        let variants = def
+            .variants
+            .iter()
+            .map(|v| (v.name.value.as_str(), v.fields.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(variants, vec![("Circle", 1), ("Empty", 0)]);
+
+        let assign = exprs.next().unwrap().AssignmentExpr().unwrap();
+        let m = assign.value.MatchExpr().unwrap();
+        assert_eq!(m.arms.len(), 3);
+        match &m.arms[0].pattern {
+            Pattern::Constructor(c) => {
+                assert_eq!(c.name.value, "Circle");
+                assert!(matches!(c.args[..], [Pattern::Binding(_)]));
+            }
+            other => panic!("expected a constructor pattern, found {other:?}"),
+        }
+        assert!(matches!(m.arms[0].guard, Some(Expr::BinOp(_))));
+        // Told apart from a binding by the type checker
+        assert!(matches!(m.arms[1].pattern, Pattern::Binding(_)));
+        assert_eq!(m.arms[1].body.len(), 1);
+        assert!(matches!(m.arms[2].pattern, Pattern::Wildcard(_)));
+    }
+
+    #[test]
+    fn test_match_patterns() {
+        assert!(parse("match x {\n}", Rule::match_expr));
+        assert!(parse("0 => print(0)", Rule::match_arm));
+        assert!(parse("\"a\" => 1", Rule::match_arm));
+        assert!(parse("true => 1", Rule::match_arm));
+        assert!(parse("-1 => 1", Rule::match_arm));
+        assert!(parse("Node(Leaf, v, _) if v > 0 => v", Rule::match_arm));
+        assert!(!parse("Node(Leaf => 1", Rule::match_arm));
+    }
+
+    #[test]
+    fn test_lambdas() {
+        assert!(parse("(x: int) => x * 2", Rule::lambda));
+        assert!(parse("() => print(1)", Rule::lambda));
+        assert!(parse("(a, b: float) => {\nprint(a)\nb\n}", Rule::lambda));
+        assert!(parse("(a: int) => (b: int) => a + b", Rule::lambda));
+        assert!(parse("(int, int) -> int", Rule::type_annotation));
+        assert!(parse("() -> none", Rule::type_annotation));
+        assert!(parse("(list<int>) -> (int) -> bool", Rule::type_annotation));
+        assert!(!parse("(x: int) x", Rule::lambda));
+    }
+
+    #[test]
+    fn test_reassignments() {
+        assert!(parse("x = 5", Rule::reassignment));
+        assert!(parse("x += y * 2", Rule::reassignment));
+        assert!(parse("p.x -= 1", Rule::reassignment));
+        assert!(parse("xs[i] *= 2", Rule::reassignment));
+        assert!(parse("grid[i][j].value /= 2", Rule::reassignment));
+        assert!(!parse("x == 5", Rule::reassignment));
+        assert!(!parse("x + = 5", Rule::reassignment));
+        assert!(parse("xs[0]", Rule::access));
+        assert!(parse("[1, 2][i + 1]", Rule::access));
+    }
+
     #[test]
     fn test_method_calls() {
         assert!(parse("print(42)", Rule::method_call));
@@ -66,6 +310,25 @@ mod tests {
         assert!(parse("rep 5 { print(42) }", Rule::rep_expr));
     }
 
+    #[test]
+    fn test_one_line_blocks() {
+        assert!(parse("{}", Rule::block));
+        assert!(parse("{ print(1) }", Rule::block));
+        assert!(parse("{\n\n print(1)\n\n}", Rule::block));
+        let rep = parse_expr("rep 5 { print(42) print(x) }")
+            .RepExpr()
+            .unwrap();
+        assert_eq!(rep.body.len(), 2);
+        let value = parse_expr("val x = if true {1} else {2}")
+            .AssignmentExpr()
+            .unwrap();
+        assert!(value.value.IfExpr().unwrap().else_block.is_some());
+        let func = parse_expr("def f(x) { return x }").FunctionDef().unwrap();
+        assert_eq!(func.body.len(), 1);
+        // The last line needs no newline
+        assert!(parse_program("print(1)\nrep 2 {}").is_ok());
+    }
+
     #[test]
     fn test_binary_operations() {
         // Arithmetic
@@ -87,6 +350,223 @@ mod tests {
         assert!(parse("x || y", Rule::bin_op));
     }
 
+    #[test]
+    fn test_binary_operation_precedence() {
+        assert_eq!(sexpr(&parse_expr("a + b * c - d")), "(- (+ a (* b c)) d)");
+        assert_eq!(sexpr(&parse_expr("a * b + c / d")), "(+ (* a b) (/ c d))");
+        assert_eq!(
+            sexpr(&parse_expr("a || b && c == d + 1")),
+            "(|| a (&& b (== c (+ d 1))))"
+        );
+        assert_eq!(
+            sexpr(&parse_expr("a < b && c >= d")),
+            "(&& (< a b) (>= c d))"
+        );
+        assert_eq!(sexpr(&parse_expr("(a + b) * c")), "(* (+ a b) c)");
+    }
+
+    #[test]
+    fn test_binary_operation_associativity() {
+        assert_eq!(sexpr(&parse_expr("a - b - c")), "(- (- a b) c)");
+        assert_eq!(sexpr(&parse_expr("a / b * c")), "(* (/ a b) c)");
+        assert_eq!(sexpr(&parse_expr("a || b || c")), "(|| (|| a b) c)");
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let src = "val x: int = 3\nval z: int = x +\n";
+        let err = parse_source("main.bdl", src).unwrap_err();
+        assert_eq!(err.file, "main.bdl");
+        assert_eq!((err.span.line, err.span.col), (2, 17));
+        assert_eq!(err.span.start, src.find("+\n").unwrap() + 1);
+        assert!(err.expected.contains(&"identifier".to_string()));
+        assert!(err.message.ends_with("found newline"));
+        assert!(err
+            .snippet
+            .ends_with("2 | val z: int = x +\n  |                 ^"));
+        assert!(err
+            .to_string()
+            .starts_with("main.bdl:2:17: error: expected"));
+    }
+
+    #[test]
+    fn test_ast_error_location() {
+        let err =
+            parse_program("val x: int = 99999999999999999999999999999999999999999\n").unwrap_err();
+        assert_eq!((err.span.line, err.span.col), (1, 14));
+        assert_eq!(err.message, "integer literal is out of range");
+        assert!(err
+            .snippet
+            .ends_with(&format!("  |              {}", "^".repeat(41))));
+    }
+
+    #[test]
+    fn test_ast_spans() {
+        let src = "val x: int = 3\nval y: int = x * 2 + 1\n";
+        let program = parse_program(src).unwrap().Program().unwrap();
+        let assign = program.expressions[1].clone().AssignmentExpr().unwrap();
+        assert_eq!((assign.span.line, assign.span.col), (2, 1));
+        assert_eq!(
+            &src[assign.span.start..assign.span.end],
+            "val y: int = x * 2 + 1"
+        );
+        assert_eq!(
+            &src[assign.target.span.start..assign.target.span.end],
+            "y: int"
+        );
+        assert_eq!(
+            (assign.target.value.span.line, assign.target.value.span.col),
+            (2, 5)
+        );
+
+        let sum = assign.value.BinOp().unwrap();
+        assert_eq!(&src[sum.span.start..sum.span.end], "x * 2 + 1");
+        let product = sum.left.BinOp().unwrap();
+        assert_eq!(&src[product.span.start..product.span.end], "x * 2");
+        assert_eq!(
+            (product.right.span().line, product.right.span().col),
+            (2, 18)
+        );
+    }
+
+    // Every rule the grammar can produce from `program` appears somewhere in
+    // this program, which must then build into an AST
+    #[test]
+    fn test_every_rule_builds() {
+        let program = r#"struct P { x: int, y: float }
+enum Shape { Dot, Circle(r: float) }
+def area(s: Shape): float {
+return match s {
+Circle(r) if r > 0.0 => r * r
+_ => 0.0
+}
+}
+val f: (int) -> bool = (n) => !(n == 1) && n != 2 || n <= 3
+var (a, b) = (1, "s")
+var xs = [-a, a + 1 - 2 * 3 / 4]
+xs[0] += xs[1]
+var p = P(1, 2.5)
+p.x = 3
+outer: rep p.x >= 2 {
+while a > 0 {
+continue outer
+}
+break
+}
+for (i, x) in enumerate(xs) {
+print(i)
+}
+for i in 0..3 step 2 {
+print(i)
+}
+if true < false {
+print(false > true)
+} else {
+print(area(Dot))
+}
+"#;
+        let mut seen = vec![];
+        let mut pairs = BdlParser::parse(Rule::program, program)
+            .unwrap()
+            .collect::<Vec<_>>();
+        while let Some(pair) = pairs.pop() {
+            seen.push(pair.as_rule());
+            pairs.extend(pair.into_inner());
+        }
+        // Silent rules never produce a pair of their own
+        let silent = include_str!("parser.pest")
+            .lines()
+            .filter_map(|line| line.split_once(" = _{"))
+            .map(|(name, _)| name)
+            .collect::<Vec<&str>>();
+        let missing = Rule::all_rules()
+            .iter()
+            .filter(|rule| !seen.contains(rule) && !silent.contains(&format!("{rule:?}").as_str()))
+            .collect::<Vec<_>>();
+        assert!(missing.is_empty(), "not covered: {missing:?}");
+
+        let program = parse_program(program).unwrap().Program().unwrap();
+        assert_eq!(program.expressions.len(), 13);
+        assert!(program.expressions[9].clone().RepExpr().is_some());
+        assert!(program.expressions[10].clone().ForExpr().is_some());
+    }
+
+    #[test]
+    fn test_loops() {
+        let w = parse_expr("while x > 0 {\nx -= 1\ncontinue\n}")
+            .WhileExpr()
+            .unwrap();
+        assert!(w.condition.BinOp().is_some());
+        assert!(w.label.is_none());
+        assert!(w.body[1].clone().ContinueExpr().unwrap().label.is_none());
+
+        let rep = parse_expr("outer: rep 3 {\ninner: while true {\nbreak outer\n}\n}")
+            .RepExpr()
+            .unwrap();
+        assert_eq!(rep.label.unwrap().value, "outer");
+        let inner = rep.body[0].clone().WhileExpr().unwrap();
+        assert_eq!(inner.label.unwrap().value, "inner");
+        let jump = inner.body[0].clone().BreakExpr().unwrap();
+        assert_eq!(jump.label.unwrap().value, "outer");
+
+        // Loop keywords are not names
+        assert!(parse_program("val while = 1\n").is_err());
+        assert!(parse_expr("val breaks = 1").AssignmentExpr().is_some());
+    }
+
+    #[test]
+    fn test_for_loops() {
+        let f = parse_expr("for x in xs {\nprint(x)\n}").ForExpr().unwrap();
+        assert_eq!(f.targets.len(), 1);
+        assert_eq!(f.targets[0].value.value, "x");
+        assert_eq!(f.targets[0].associated_type, Type::Infer);
+        assert!(f.iterable.Identifier().is_some());
+        assert!(f.label.is_none());
+
+        let f = parse_expr("rows: for (i, row: list<int>) in enumerate(grid) {\nbreak rows\n}")
+            .ForExpr()
+            .unwrap();
+        assert_eq!(f.label.unwrap().value, "rows");
+        assert_eq!(f.targets[0].value.value, "i");
+        assert_eq!(
+            f.targets[1].associated_type,
+            Type::List(Box::new(Type::Int))
+        );
+        assert!(f.iterable.MethodCallExpr().is_some());
+
+        // `..` binds looser than arithmetic, and `step` looser still
+        let range = parse_expr("0..n + 1 step 2").RangeExpr().unwrap();
+        assert!(range.start.Integer().is_some());
+        assert_eq!(range.stop.BinOp().unwrap().op, "+");
+        assert!(range.step.unwrap().Integer().is_some());
+        assert!(parse_expr("a..b").RangeExpr().unwrap().step.is_none());
+
+        let err = parse_program("val s = n step 2\n").unwrap_err();
+        assert_eq!(err.message, "`step` can only follow a range");
+        assert!(parse_program("val in = 1\n").is_err());
+        assert!(parse_expr("val steps = 1").AssignmentExpr().is_some());
+    }
+
+    #[test]
+    fn test_rep_calls_and_unary_operations() {
+        let rep = parse_expr("rep count(3) {\nprint(-x)\nf()\n}")
+            .RepExpr()
+            .unwrap();
+        let count = rep.num_iterations.MethodCallExpr().unwrap();
+        assert_eq!(count.method_name.value, "count");
+        assert_eq!(count.args.len(), 1);
+        assert_eq!(rep.body.len(), 2);
+        let print = rep.body[0].clone().PrintExpr().unwrap();
+        let negated = print.arg.UnOp().unwrap();
+        assert_eq!(negated.op, "-");
+        assert!(rep.body[1]
+            .clone()
+            .MethodCallExpr()
+            .unwrap()
+            .args
+            .is_empty());
+    }
+
     #[test]
     fn test_unary_operations() {
         assert!(parse("!true", Rule::un_op));
@@ -137,6 +617,6 @@ mod tests {
             result: int = add(5, 3)
             print(result)
         "#;
-        // assert!(parse(program_with_multiple_functions, Rule::program));
+        assert!(parse(program_with_multiple_functions, Rule::program));
     }
 }
@@ -0,0 +1,1981 @@
+use crate::ast::{
+    AssignmentExpr, BinOpExpr, Capture, ConstructorPattern, DestructureExpr, EnumDef, Expr,
+    FieldAccessExpr, ForExpr, FunctionDef, Identifier, IfExpr, IndexExpr, LambdaExpr, ListExpr,
+    MatchExpr, MethodCallExpr, Pattern, Program, RangeExpr, ReassignmentExpr, RepExpr, ReturnExpr,
+    Span, StructDef, TupleExpr, Type, TypedIdentifier, UnOpExpr, WhileExpr,
+};
+use crate::error::render_snippet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{}:{}: error: {message}", .span.line, .span.col)]
+pub struct TypeError {
+    pub span: Span,
+    pub message: String,
+    // Other places in the source that explain the error, such as the
+    // declaration of a binding it is about
+    pub notes: Vec<(Span, String)>,
+}
+
+impl TypeError {
+    pub fn new(span: Span, message: impl Into<String>) -> TypeError {
+        TypeError {
+            span,
+            message: message.into(),
+            notes: vec![],
+        }
+    }
+
+    pub fn with_note(mut self, span: Span, message: impl Into<String>) -> TypeError {
+        self.notes.push((span, message.into()));
+        self
+    }
+
+    // Formats the error like a ParseError, quoting the offending source line,
+    // followed by each of its notes
+    pub fn render(&self, file: &str, source: &str) -> String {
+        let mut rendered = format!("{file}:{self}\n{}", render_snippet(source, &self.span));
+        for (span, message) in &self.notes {
+            rendered += &format!(
+                "\n{file}:{}:{}: note: {message}\n{}",
+                span.line,
+                span.col,
+                render_snippet(source, span)
+            );
+        }
+        rendered
+    }
+}
+
+// Checks every expression in `program`, collecting all type errors rather
+// than stopping at the first one. Inferred types are written back into the
+// bindings and parameters that left them out
+pub fn check_program(program: &mut Program) -> Result<(), Vec<TypeError>> {
+    let mut checker = TypeChecker::new();
+    checker.check_top_level(&mut program.expressions);
+    checker.check_deferred();
+    checker.apply_program(&mut program.expressions);
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        // Functions are checked before the statements around them, so the
+        // errors are put back into source order
+        checker.errors.sort_by_key(|e| e.span.start);
+        Err(checker.errors)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    // A `def`, a struct constructor or an enum variant
+    Function,
+    Val,
+    Var,
+    // Names that cannot be changed any more than a `val`, but that have no
+    // `var` form to suggest instead
+    Parameter,
+    LoopTarget,
+    PatternBinding,
+}
+
+impl Kind {
+    fn is_constant(self) -> bool {
+        matches!(
+            self,
+            Kind::Val | Kind::Parameter | Kind::LoopTarget | Kind::PatternBinding
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    ty: Type,
+    kind: Kind,
+    // Where the name was declared, for errors about writing to it
+    span: Span,
+    // Type variables of a generalized function, replaced with fresh ones at
+    // every use, along with the operators its body applies to them
+    generics: Vec<u32>,
+    constraints: Vec<OperatorUse>,
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    bindings: HashMap<String, Binding>,
+    // Function bodies are hoisted out of their enclosing block, so the
+    // variables declared outside of one are not visible inside it
+    function_boundary: bool,
+    // Lambdas can use everything around them, and record what they do use
+    lambda: bool,
+    captures: Vec<Capture>,
+    // Set on the body of a loop to where the loop starts, along with its
+    // label if it has one
+    loop_start: Option<usize>,
+    loop_label: Option<String>,
+}
+
+// An operator that only applies to some types, checked once the type of its
+// operands is known
+#[derive(Debug, Clone)]
+struct OperatorUse {
+    op: String,
+    operand: Type,
+    binary: bool,
+    span: Span,
+}
+
+struct TypeChecker {
+    scopes: Vec<Scope>,
+    // Return type of each enclosing function body
+    returns: Vec<Type>,
+    // What each type variable has been unified with so far
+    subst: Vec<Option<Type>>,
+    // Operators whose operand types were still unknown where they appeared
+    deferred: Vec<OperatorUse>,
+    // Names declared at the top level, which functions cannot use
+    top_level_variables: HashSet<String>,
+    // Fields of every struct, in the order they were declared
+    structs: HashMap<String, Vec<(String, Type)>>,
+    // Variants of every enum with the types of their fields, in the order
+    // they were declared, and the enum declaring each variant
+    enums: HashMap<String, Vec<(String, Vec<Type>)>>,
+    variants: HashMap<String, String>,
+    // Where each `var` that a lambda captures is declared
+    shared: HashSet<usize>,
+    errors: Vec<TypeError>,
+}
+
+fn applies(op: &str, operand: &Type) -> bool {
+    match op {
+        "+" | "<" | ">" | "<=" | ">=" | "min" | "max" | "sorted" => {
+            matches!(operand, Type::Int | Type::Float | Type::String)
+        }
+        _ => matches!(operand, Type::Int | Type::Float),
+    }
+}
+
+// Functions every program can call without declaring them. A program can
+// still declare its own function or variable of the same name
+const BUILTINS: &[&str] = &[
+    "map",
+    "filter",
+    "reduce",
+    "fold",
+    "any",
+    "all",
+    "zip",
+    "enumerate",
+    "sum",
+    "min",
+    "max",
+    "sorted",
+    "push",
+    "pop",
+];
+
+// `t` with every type variable replaced by `f`
+fn map_vars(t: &Type, f: &mut impl FnMut(u32) -> Type) -> Type {
+    match t {
+        Type::Var(v) => f(*v),
+        Type::List(t) => Type::List(Box::new(map_vars(t, f))),
+        Type::Tuple(ts) => Type::Tuple(ts.iter().map(|t| map_vars(t, f)).collect()),
+        Type::FunctionType(args, ret) => Type::FunctionType(
+            args.iter().map(|a| map_vars(a, f)).collect(),
+            Box::new(ret.as_ref().as_ref().map(|r| map_vars(r, f))),
+        ),
+        t => t.clone(),
+    }
+}
+
+// Appends the type variables in `t` that are not already in `out`
+fn free_vars(t: &Type, out: &mut Vec<u32>) {
+    map_vars(t, &mut |v| {
+        if !out.contains(&v) {
+            out.push(v);
+        }
+        Type::Var(v)
+    });
+}
+
+fn contains_infer(t: &Type) -> bool {
+    match t {
+        Type::Infer => true,
+        Type::List(t) => contains_infer(t),
+        Type::Tuple(ts) => ts.iter().any(contains_infer),
+        Type::FunctionType(args, ret) => {
+            args.iter().any(contains_infer) || ret.as_ref().as_ref().is_some_and(contains_infer)
+        }
+        _ => false,
+    }
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        format!("{count} {word}")
+    } else {
+        format!("{count} {word}s")
+    }
+}
+
+// A pattern as far as exhaustiveness is concerned. Bindings match anything,
+// and literals are constructors of a type with too many to list
+#[derive(Debug, Clone)]
+enum Pat {
+    Any,
+    Ctor(String, Vec<Pat>),
+}
+
+impl fmt::Display for Pat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pat::Any => write!(f, "_"),
+            Pat::Ctor(name, args) if args.is_empty() => write!(f, "{name}"),
+            Pat::Ctor(name, args) => {
+                let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+                write!(f, "{name}({})", args.join(", "))
+            }
+        }
+    }
+}
+
+// The rows whose first pattern matches constructor `name`, with that
+// pattern replaced by its `arity` arguments
+fn specialize(rows: &[Vec<Pat>], name: &str, arity: usize) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let mut args = match &row[0] {
+                Pat::Ctor(n, args) if n == name => args.clone(),
+                Pat::Ctor(..) => return None,
+                Pat::Any => vec![Pat::Any; arity],
+            };
+            args.extend_from_slice(&row[1..]);
+            Some(args)
+        })
+        .collect()
+}
+
+// Names of the variables and functions `expr` refers to
+fn referenced_names<'a>(expr: &'a Expr, out: &mut HashSet<&'a str>) {
+    match expr {
+        Expr::Identifier(id) => {
+            out.insert(&id.value);
+        }
+        Expr::MethodCallExpr(call) => {
+            out.insert(&call.method_name.value);
+        }
+        _ => {}
+    }
+    for child in expr.children() {
+        referenced_names(child, out);
+    }
+}
+
+// Indices of the top-level functions in `exprs`, grouped into sets that call
+// each other and ordered so that every function comes after those it calls
+fn function_groups(exprs: &[Expr]) -> Vec<Vec<usize>> {
+    let mut functions = HashMap::new();
+    for (i, expr) in exprs.iter().enumerate() {
+        if let Expr::FunctionDef(func) = expr {
+            functions.entry(func.name.value.as_str()).or_insert(i);
+        }
+    }
+    // A function defined twice is still checked, but calls go to the first
+    let defs = exprs
+        .iter()
+        .enumerate()
+        .filter(|(_, expr)| matches!(expr, Expr::FunctionDef(_)))
+        .map(|(i, _)| i)
+        .collect::<Vec<usize>>();
+    let mut calls = HashMap::new();
+    for &i in &defs {
+        let mut names = HashSet::new();
+        referenced_names(&exprs[i], &mut names);
+        let mut callees = names
+            .into_iter()
+            .filter_map(|name| functions.get(name).copied())
+            .collect::<Vec<usize>>();
+        callees.sort();
+        calls.insert(i, callees);
+    }
+
+    let mut tarjan = Tarjan {
+        calls: &calls,
+        index: HashMap::new(),
+        low: HashMap::new(),
+        stack: vec![],
+        groups: vec![],
+    };
+    for root in defs {
+        if !tarjan.index.contains_key(&root) {
+            tarjan.visit(root);
+        }
+    }
+    tarjan.groups
+}
+
+// Tarjan's strongly connected components, which are found callees first
+struct Tarjan<'a> {
+    calls: &'a HashMap<usize, Vec<usize>>,
+    index: HashMap<usize, usize>,
+    low: HashMap<usize, usize>,
+    stack: Vec<usize>,
+    groups: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, v: usize) {
+        let index = self.index.len();
+        self.index.insert(v, index);
+        self.low.insert(v, index);
+        self.stack.push(v);
+        let calls = self.calls;
+        for &w in &calls[&v] {
+            if !self.index.contains_key(&w) {
+                self.visit(w);
+                self.low.insert(v, self.low[&v].min(self.low[&w]));
+            } else if self.stack.contains(&w) {
+                self.low.insert(v, self.low[&v].min(self.index[&w]));
+            }
+        }
+        if self.low[&v] == index {
+            let at = self.stack.iter().position(|&w| w == v).unwrap();
+            let mut group = self.stack.split_off(at);
+            group.sort();
+            self.groups.push(group);
+        }
+    }
+}
+
+impl TypeChecker {
+    fn new() -> TypeChecker {
+        TypeChecker {
+            scopes: vec![Scope::default()],
+            returns: vec![],
+            subst: vec![],
+            deferred: vec![],
+            top_level_variables: HashSet::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            variants: HashMap::new(),
+            shared: HashSet::new(),
+            errors: vec![],
+        }
+    }
+
+    fn error(&mut self, span: Span, message: impl Into<String>) {
+        self.errors.push(TypeError::new(span, message));
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.subst.push(None);
+        Type::Var(self.subst.len() as u32 - 1)
+    }
+
+    // `t` with every type variable that has been unified replaced by its type
+    fn resolve(&self, t: &Type) -> Type {
+        map_vars(t, &mut |v| match &self.subst[v as usize] {
+            Some(t) => self.resolve(t),
+            None => Type::Var(v),
+        })
+    }
+
+    // Makes `a` and `b` the same type, or returns false if they conflict.
+    // `Infer` stands for an expression that already failed to check and
+    // agrees with everything; variables unified with it are not reported
+    // as ambiguous later
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        let (a, b) = (self.resolve(a), self.resolve(b));
+        match (&a, &b) {
+            (Type::Var(x), Type::Var(y)) if x == y => true,
+            (Type::Var(v), t) | (t, Type::Var(v)) => {
+                let mut vars = vec![];
+                free_vars(t, &mut vars);
+                if vars.contains(v) {
+                    return false;
+                }
+                self.subst[*v as usize] = Some(t.clone());
+                true
+            }
+            (Type::Infer, _) | (_, Type::Infer) => true,
+            (Type::List(a), Type::List(b)) => self.unify(a, b),
+            (Type::Tuple(a), Type::Tuple(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.unify(a, b))
+            }
+            (Type::FunctionType(a_args, a_ret), Type::FunctionType(b_args, b_ret)) => {
+                a_args.len() == b_args.len()
+                    && a_args.iter().zip(b_args).all(|(a, b)| self.unify(a, b))
+                    && match (a_ret.as_ref(), b_ret.as_ref()) {
+                        (Some(a), Some(b)) => self.unify(a, b),
+                        _ => true,
+                    }
+            }
+            _ => a == b,
+        }
+    }
+
+    fn expect(&mut self, span: Span, expected: &Type, found: &Type) {
+        if !self.unify(expected, found) {
+            let (expected, found) = (self.resolve(expected), self.resolve(found));
+            self.error(
+                span,
+                format!("mismatched types: expected {expected}, found {found}"),
+            );
+        }
+    }
+
+    // Reports a `none` where a value is kept or passed on, returning whether
+    // `found` is a value
+    fn expect_value(&mut self, span: Span, found: &Type, message: String) -> bool {
+        if self.resolve(found) == Type::None {
+            self.error(span, message);
+            return false;
+        }
+        true
+    }
+
+    fn check_operator(&mut self, operator: OperatorUse) {
+        match self.resolve(&operator.operand) {
+            Type::Infer => {}
+            Type::Var(_) => self.deferred.push(operator),
+            // Printing needs every part of the value, so it waits for all of
+            // them to be known
+            operand if operator.op == "print" => {
+                let mut vars = vec![];
+                free_vars(&operand, &mut vars);
+                if self.holds_function(&operand, &mut vec![]) {
+                    let message = format!("cannot print a value of type {operand}");
+                    self.error(operator.span, message);
+                } else if !vars.is_empty() {
+                    self.deferred.push(operator);
+                }
+            }
+            operand => {
+                if !applies(&operator.op, &operand) {
+                    let message = if operator.binary {
+                        format!("cannot apply `{}` to {operand} and {operand}", operator.op)
+                    } else {
+                        format!("cannot apply `{}` to {operand}", operator.op)
+                    };
+                    self.error(operator.span, message);
+                }
+            }
+        }
+    }
+
+    // Whether a value of type `t` has a function in it, which has nothing to
+    // print. `seen` holds the structs and enums already being looked into
+    fn holds_function(&self, t: &Type, seen: &mut Vec<String>) -> bool {
+        match t {
+            Type::FunctionType(..) => true,
+            Type::List(t) => self.holds_function(t, seen),
+            Type::Tuple(ts) => ts.iter().any(|t| self.holds_function(t, seen)),
+            Type::Named(name) if !seen.contains(name) => {
+                seen.push(name.clone());
+                let mut fields = vec![];
+                if let Some(struct_fields) = self.structs.get(name) {
+                    fields.extend(struct_fields.iter().map(|(_, t)| t.clone()));
+                }
+                if let Some(variants) = self.enums.get(name) {
+                    fields.extend(variants.iter().flat_map(|(_, ts)| ts.clone()));
+                }
+                fields.iter().any(|t| self.holds_function(t, seen))
+            }
+            _ => false,
+        }
+    }
+
+    // Operators whose operands were only inferred after they were checked
+    fn check_deferred(&mut self) {
+        for mut operator in std::mem::take(&mut self.deferred) {
+            operator.operand = self.resolve(&operator.operand);
+            if !matches!(operator.operand, Type::Var(_)) {
+                self.check_operator(operator);
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Identifier, ty: Type, kind: Kind) {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.bindings.contains_key(&name.value) {
+            self.error(
+                name.span,
+                format!("`{}` is already defined in this scope", name.value),
+            );
+            return;
+        }
+        let binding = Binding {
+            ty,
+            kind,
+            span: name.span,
+            generics: vec![],
+            constraints: vec![],
+        };
+        scope.bindings.insert(name.value.clone(), binding);
+    }
+
+    fn lookup(&mut self, name: &Identifier) -> Type {
+        let mut crossed_function = false;
+        let mut lambdas = vec![];
+        let mut found = None;
+        for (depth, scope) in self.scopes.iter().enumerate().rev() {
+            if let Some(binding) = scope.bindings.get(&name.value) {
+                found = Some((depth, binding.clone()));
+                break;
+            }
+            crossed_function |= scope.function_boundary;
+            if scope.lambda {
+                lambdas.push(depth);
+            }
+        }
+        match found {
+            Some((_, binding)) if !crossed_function || binding.kind == Kind::Function => {
+                // Functions, local ones included, are static in C++, and
+                // everything else is local to the C++ function the lambda is
+                // in
+                if binding.kind != Kind::Function {
+                    let capture = Capture {
+                        name: name.value.clone(),
+                        by_reference: !binding.kind.is_constant(),
+                    };
+                    if capture.by_reference && !lambdas.is_empty() {
+                        self.shared.insert(binding.span.start);
+                    }
+                    for depth in lambdas {
+                        let captures = &mut self.scopes[depth].captures;
+                        if !captures.contains(&capture) {
+                            captures.push(capture.clone());
+                        }
+                    }
+                }
+                self.instantiate(&binding, name.span)
+            }
+            None if BUILTINS.contains(&name.value.as_str()) => {
+                let message = format!("built-in `{}` can only be called", name.value);
+                self.error(name.span, message);
+                Type::Infer
+            }
+            // Top-level functions are checked before the variables around
+            // them are declared
+            None if !(crossed_function && self.top_level_variables.contains(&name.value)) => {
+                self.error(
+                    name.span,
+                    format!("cannot find `{}` in this scope", name.value),
+                );
+                Type::Infer
+            }
+            _ => {
+                let message = format!(
+                    "`{}` is declared outside of this function and cannot be used inside it",
+                    name.value
+                );
+                self.error(name.span, message);
+                Type::Infer
+            }
+        }
+    }
+
+    // The type of a use of `binding`, with fresh type variables for its
+    // generic ones
+    fn instantiate(&mut self, binding: &Binding, span: Span) -> Type {
+        let mut fresh = HashMap::new();
+        for &v in &binding.generics {
+            let var = self.fresh();
+            fresh.insert(v, var);
+        }
+        let substitute =
+            |t: &Type| map_vars(t, &mut |v| fresh.get(&v).cloned().unwrap_or(Type::Var(v)));
+        let ty = substitute(&binding.ty);
+        let constraints = binding
+            .constraints
+            .iter()
+            .map(|c| OperatorUse {
+                operand: substitute(&c.operand),
+                span,
+                ..c.clone()
+            })
+            .collect::<Vec<OperatorUse>>();
+        for constraint in constraints {
+            self.check_operator(constraint);
+        }
+        ty
+    }
+
+    // Structs and enums can be used anywhere in the program, so they are all
+    // known before anything else is checked
+    fn declare_types(&mut self, exprs: &[Expr]) {
+        let enums = exprs
+            .iter()
+            .filter_map(|expr| match expr {
+                Expr::EnumDef(def) => Some(def),
+                _ => None,
+            })
+            .collect::<Vec<&EnumDef>>();
+        for def in &enums {
+            let variants = def
+                .variants
+                .iter()
+                .map(|v| {
+                    let fields = v.fields.iter().map(|f| f.associated_type.clone());
+                    (v.name.value.clone(), fields.collect())
+                })
+                .collect();
+            if self.enums.contains_key(&def.name.value) {
+                let message = format!("`{}` is already defined in this scope", def.name.value);
+                self.error(def.name.span, message);
+            } else {
+                self.enums.insert(def.name.value.clone(), variants);
+            }
+        }
+        self.declare_structs(exprs);
+        for def in enums {
+            self.declare_enum(def);
+        }
+    }
+
+    // Each struct declares a constructor that takes its fields in order
+    fn declare_structs(&mut self, exprs: &[Expr]) {
+        let defs = exprs
+            .iter()
+            .filter_map(|expr| match expr {
+                Expr::StructDef(def) => Some(def),
+                _ => None,
+            })
+            .collect::<Vec<&StructDef>>();
+        for def in &defs {
+            let fields = def
+                .fields
+                .iter()
+                .map(|f| (f.value.value.clone(), f.associated_type.clone()))
+                .collect();
+            // A second struct of the same name is reported by its constructor
+            self.structs.entry(def.name.value.clone()).or_insert(fields);
+            if self.enums.contains_key(&def.name.value) {
+                let message = format!("`{}` is already defined in this scope", def.name.value);
+                self.error(def.name.span, message);
+            }
+        }
+        for def in defs {
+            let name = &def.name.value;
+            let mut seen = HashSet::new();
+            for field in &def.fields {
+                if !seen.insert(&field.value.value) {
+                    let message = format!(
+                        "field `{}` is already declared in `{name}`",
+                        field.value.value
+                    );
+                    self.error(field.value.span, message);
+                }
+                self.check_type_names(field.span, &field.associated_type);
+            }
+            if def
+                .fields
+                .iter()
+                .any(|f| self.holds(&f.associated_type, name, &mut HashSet::new()))
+            {
+                let message = format!("`{name}` contains itself, so it would be infinitely large");
+                self.error(def.name.span, message);
+            }
+            let args = def.fields.iter().map(|f| f.associated_type.clone());
+            let ty = Type::FunctionType(args.collect(), Box::new(Some(Type::Named(name.clone()))));
+            self.declare(&def.name, ty, Kind::Function);
+        }
+    }
+
+    // Every variant declares a constructor, which is a plain value for those
+    // without fields. Enums hold their variants behind a pointer, so they
+    // may contain themselves
+    fn declare_enum(&mut self, def: &EnumDef) {
+        let name = &def.name.value;
+        for variant in &def.variants {
+            let mut seen = HashSet::new();
+            for field in &variant.fields {
+                if !seen.insert(&field.value.value) {
+                    let message = format!(
+                        "field `{}` is already declared in `{}`",
+                        field.value.value, variant.name.value
+                    );
+                    self.error(field.value.span, message);
+                }
+                self.check_type_names(field.span, &field.associated_type);
+            }
+            let value = Type::Named(name.clone());
+            let ty = if variant.fields.is_empty() {
+                value
+            } else {
+                let args = variant.fields.iter().map(|f| f.associated_type.clone());
+                Type::FunctionType(args.collect(), Box::new(Some(value)))
+            };
+            self.declare(&variant.name, ty, Kind::Function);
+            self.variants
+                .entry(variant.name.value.clone())
+                .or_insert(name.clone());
+        }
+    }
+
+    // Whether a value of type `t` holds a `name` in place rather than in a
+    // list. `seen` keeps a struct that contains another one from looping
+    fn holds(&self, t: &Type, name: &str, seen: &mut HashSet<String>) -> bool {
+        match t {
+            Type::Named(n) if n == name => true,
+            Type::Named(n) => {
+                seen.insert(n.clone())
+                    && self
+                        .structs
+                        .get(n)
+                        .is_some_and(|fields| fields.iter().any(|(_, t)| self.holds(t, name, seen)))
+            }
+            Type::Tuple(ts) => ts.iter().any(|t| self.holds(t, name, seen)),
+            _ => false,
+        }
+    }
+
+    // Reports the names in an annotation that are not structs, and any
+    // `none` but what a function returns, returning whether there were none
+    fn check_type_names(&mut self, span: Span, t: &Type) -> bool {
+        match t {
+            Type::None => {
+                self.error(span, "`none` is only allowed as a return type");
+                false
+            }
+            Type::Named(name)
+                if !self.structs.contains_key(name) && !self.enums.contains_key(name) =>
+            {
+                self.error(span, format!("cannot find type `{name}` in this scope"));
+                false
+            }
+            Type::List(t) => self.check_type_names(span, t),
+            // Every part is checked, so that each unknown name is reported
+            Type::Tuple(ts) => {
+                let checked = ts
+                    .iter()
+                    .map(|t| self.check_type_names(span, t))
+                    .collect::<Vec<bool>>();
+                checked.iter().all(|ok| *ok)
+            }
+            Type::FunctionType(args, ret) => {
+                let checked = args
+                    .iter()
+                    .chain(ret.iter().filter(|t| **t != Type::None))
+                    .map(|t| self.check_type_names(span, t))
+                    .collect::<Vec<bool>>();
+                checked.iter().all(|ok| *ok)
+            }
+            _ => true,
+        }
+    }
+
+    // Functions can be called anywhere in the block that defines them, so
+    // their signatures are declared before any expression is checked.
+    // Unannotated parameters and return types start out unknown
+    fn declare_functions(&mut self, exprs: &mut [Expr]) {
+        for expr in exprs.iter_mut() {
+            if let Expr::FunctionDef(func) = expr {
+                // An annotation naming a missing struct is not checked further
+                for arg in func.args.iter_mut() {
+                    if arg.associated_type == Type::Infer {
+                        arg.associated_type = self.fresh();
+                    } else if !self.check_type_names(arg.span, &arg.associated_type) {
+                        arg.associated_type = Type::Infer;
+                    }
+                }
+                if let Some(ret) = func.return_type.as_ref().filter(|t| **t != Type::None) {
+                    if !self.check_type_names(func.name.span, &ret.clone()) {
+                        func.return_type = Some(Type::Infer);
+                    }
+                }
+                let args = func.args.iter().map(|a| a.associated_type.clone());
+                if func.return_type.is_none() {
+                    func.return_type = Some(self.fresh());
+                }
+                let ty = Type::FunctionType(args.collect(), Box::new(func.return_type.clone()));
+                self.declare(&func.name, ty, Kind::Function);
+            }
+        }
+    }
+
+    // Top-level functions are checked first, callees before callers, and
+    // generalized as soon as they are checked so that every later use can
+    // instantiate them at different types
+    fn check_top_level(&mut self, exprs: &mut [Expr]) {
+        self.declare_types(exprs);
+        for expr in exprs.iter() {
+            if let Expr::AssignmentExpr(assign) = expr {
+                let name = assign.target.value.value.clone();
+                self.top_level_variables.insert(name);
+            }
+        }
+        self.declare_functions(exprs);
+        for group in function_groups(exprs) {
+            for &i in &group {
+                if let Expr::FunctionDef(func) = &mut exprs[i] {
+                    self.check_function(func);
+                }
+            }
+            let names = group
+                .iter()
+                .filter_map(|&i| match &exprs[i] {
+                    Expr::FunctionDef(func) => Some(func.name.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<Identifier>>();
+            self.generalize(&names);
+        }
+        for expr in exprs.iter_mut() {
+            if !matches!(expr, Expr::FunctionDef(_)) {
+                self.check_expr(expr);
+            }
+        }
+    }
+
+    fn generalize(&mut self, names: &[Identifier]) {
+        // Variables that appear elsewhere in scope may still be unified, so
+        // only the rest belong to the group
+        let mut environment = vec![];
+        for scope in &self.scopes {
+            for (name, binding) in &scope.bindings {
+                if names.iter().all(|n| &n.value != name) {
+                    let mut vars = vec![];
+                    free_vars(&self.resolve(&binding.ty), &mut vars);
+                    vars.retain(|v| !binding.generics.contains(v));
+                    environment.extend(vars);
+                }
+            }
+        }
+
+        let mut generalized = vec![];
+        for name in names {
+            let ty = match self.scopes[0].bindings.get(&name.value) {
+                Some(binding) => self.resolve(&binding.ty),
+                None => continue,
+            };
+            let mut generics = vec![];
+            free_vars(&ty, &mut generics);
+            generics.retain(|v| !environment.contains(v));
+
+            // C++ deduces template parameters from the arguments, so every
+            // generic type has to show up in a parameter
+            if let Type::FunctionType(args, ret) = &ty {
+                let mut in_args = vec![];
+                args.iter().for_each(|a| free_vars(a, &mut in_args));
+                let mut in_ret = vec![];
+                if let Some(ret) = ret.as_ref() {
+                    free_vars(ret, &mut in_ret);
+                }
+                if in_ret
+                    .iter()
+                    .any(|v| generics.contains(v) && !in_args.contains(v))
+                {
+                    let ret = ret.as_ref().as_ref().unwrap();
+                    self.return_type_error(name, ret);
+                }
+            }
+
+            let constraints = self
+                .deferred
+                .iter()
+                .filter(|c| {
+                    let mut vars = vec![];
+                    free_vars(&self.resolve(&c.operand), &mut vars);
+                    vars.iter().any(|v| generics.contains(v))
+                })
+                .map(|c| OperatorUse {
+                    operand: self.resolve(&c.operand),
+                    ..c.clone()
+                })
+                .collect();
+            generalized.extend(generics.iter().copied());
+            let binding = self.scopes[0].bindings.get_mut(&name.value).unwrap();
+            binding.ty = ty;
+            binding.generics = generics;
+            binding.constraints = constraints;
+        }
+
+        // Operators on generic types are checked again wherever the
+        // function is used instead
+        let deferred = std::mem::take(&mut self.deferred);
+        self.deferred = deferred
+            .into_iter()
+            .filter(|c| {
+                let mut vars = vec![];
+                free_vars(&self.resolve(&c.operand), &mut vars);
+                !vars.iter().any(|v| generalized.contains(v))
+            })
+            .collect();
+    }
+
+    // Writes the inferred types back into the AST, reporting any binding
+    // whose type was never decided. Type variables are only allowed in the
+    // top-level generic functions that own them
+    fn apply_program(&mut self, exprs: &mut [Expr]) {
+        for expr in exprs.iter_mut() {
+            let generics = match expr {
+                Expr::FunctionDef(func) => self.scopes[0]
+                    .bindings
+                    .get(&func.name.value)
+                    .map(|b| b.generics.clone())
+                    .unwrap_or_default(),
+                _ => vec![],
+            };
+            self.apply(expr, &generics);
+        }
+    }
+
+    fn apply(&mut self, expr: &mut Expr, generics: &[u32]) {
+        match expr {
+            Expr::AssignmentExpr(assign) => {
+                self.apply_binding(&mut assign.target, generics, "");
+                assign.shared = self.shared.contains(&assign.target.value.span.start);
+            }
+            Expr::DestructureExpr(destructure) => {
+                for target in destructure.targets.iter_mut() {
+                    self.apply_binding(target, generics, "");
+                    destructure.shared |= self.shared.contains(&target.value.span.start);
+                }
+            }
+            Expr::ForExpr(f) => {
+                for target in f.targets.iter_mut() {
+                    self.apply_binding(target, generics, "");
+                }
+            }
+            Expr::IfExpr(if_expr) => if_expr.ty = self.resolve(&if_expr.ty),
+            Expr::ListExpr(list) => {
+                // Nothing decides the elements of an empty list that is only
+                // printed or compared, and any type would do there
+                let mut vars = vec![];
+                free_vars(&self.resolve(&list.ty), &mut vars);
+                for v in vars.into_iter().filter(|v| !generics.contains(v)) {
+                    self.unify(&Type::Var(v), &Type::Int);
+                }
+                list.ty = self.resolve(&list.ty);
+            }
+            Expr::MatchExpr(m) => {
+                m.ty = self.resolve(&m.ty);
+                for arm in m.arms.iter_mut() {
+                    self.apply_pattern(&mut arm.pattern, generics);
+                }
+            }
+            Expr::LambdaExpr(lambda) => {
+                for param in lambda.params.iter_mut() {
+                    self.apply_binding(param, generics, "parameter ");
+                }
+                lambda.return_type = self.resolve(&lambda.return_type);
+            }
+            Expr::FunctionDef(func) => {
+                for arg in func.args.iter_mut() {
+                    self.apply_binding(arg, generics, "parameter ");
+                }
+                if let Some(ret) = &func.return_type {
+                    let ret = self.resolve(ret);
+                    let mut vars = vec![];
+                    free_vars(&ret, &mut vars);
+                    if vars.iter().any(|v| !generics.contains(v)) {
+                        self.return_type_error(&func.name, &ret);
+                    }
+                    func.return_type = Some(ret);
+                }
+            }
+            _ => {}
+        }
+        for child in expr.children_mut() {
+            self.apply(child, generics);
+        }
+    }
+
+    fn apply_pattern(&mut self, pattern: &mut Pattern, generics: &[u32]) {
+        match pattern {
+            Pattern::Binding(binding) => self.apply_binding(binding, generics, ""),
+            Pattern::Constructor(c) => {
+                for arg in c.args.iter_mut() {
+                    self.apply_pattern(arg, generics);
+                }
+            }
+            Pattern::Wildcard(_) | Pattern::Literal(_) => {}
+        }
+    }
+
+    fn apply_binding(&mut self, target: &mut TypedIdentifier, generics: &[u32], kind: &str) {
+        let ty = self.resolve(&target.associated_type);
+        let mut vars = vec![];
+        free_vars(&ty, &mut vars);
+        if !contains_infer(&ty) && vars.iter().any(|v| !generics.contains(v)) {
+            let message = format!(
+                "cannot infer the type of {kind}`{}` from {ty}, add a type annotation",
+                target.value.value
+            );
+            self.error(target.span, message);
+        }
+        target.associated_type = ty;
+    }
+
+    fn return_type_error(&mut self, name: &Identifier, ret: &Type) {
+        let message = format!(
+            "cannot infer the return type of `{}` from {ret}, add a return type annotation",
+            name.value
+        );
+        self.error(name.span, message);
+    }
+
+    // Checks `exprs` in the current scope, returning the type of the last one
+    fn check_exprs(&mut self, exprs: &mut [Expr]) -> Type {
+        self.declare_functions(exprs);
+        let mut last = Type::None;
+        for expr in exprs.iter_mut() {
+            last = self.check_expr(expr);
+        }
+        last
+    }
+
+    fn check_block(&mut self, exprs: &mut [Expr]) -> Type {
+        self.scopes.push(Scope::default());
+        let ty = self.check_exprs(exprs);
+        self.scopes.pop();
+        ty
+    }
+
+    fn check_loop_body(&mut self, body: &mut [Expr], label: &Option<Identifier>, span: Span) {
+        self.enter_loop(label, span);
+        self.check_exprs(body);
+        self.scopes.pop();
+    }
+
+    // Pushes the scope of a loop's body, which the caller pops
+    fn enter_loop(&mut self, label: &Option<Identifier>, span: Span) {
+        if let Some(label) = label {
+            if self.enclosing_loop(Some(&label.value)).is_some() {
+                let message = format!(
+                    "label `{}` is already used by an enclosing loop",
+                    label.value
+                );
+                self.error(label.span, message);
+            }
+        }
+        self.scopes.push(Scope {
+            loop_start: Some(span.start),
+            loop_label: label.as_ref().map(|l| l.value.clone()),
+            ..Scope::default()
+        });
+    }
+
+    // Where the innermost loop, or the one labelled `label`, starts. Loops
+    // outside of the function or lambda being checked cannot be reached
+    fn enclosing_loop(&self, label: Option<&String>) -> Option<usize> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(start) = scope.loop_start {
+                if label.is_none() || label == scope.loop_label.as_ref() {
+                    return Some(start);
+                }
+            }
+            if scope.function_boundary || scope.lambda {
+                break;
+            }
+        }
+        None
+    }
+
+    // `keyword` is `break` or `continue`, and `target` is set to where the
+    // loop it leaves starts
+    fn check_jump(
+        &mut self,
+        keyword: &str,
+        label: &Option<Identifier>,
+        span: Span,
+        target: &mut usize,
+    ) -> Type {
+        let name = label.as_ref().map(|l| &l.value);
+        if let Some(start) = self.enclosing_loop(name) {
+            *target = start;
+            // Like a `return`, a jump never produces a value where it
+            // appears, so it fits wherever it is used
+            return self.fresh();
+        }
+        match label {
+            Some(label) if self.enclosing_loop(None).is_some() => {
+                let message = format!("no loop labelled `{}` around this `{keyword}`", label.value);
+                self.error(label.span, message);
+            }
+            _ => self.error(span, format!("`{keyword}` outside of a loop")),
+        }
+        Type::None
+    }
+
+    fn check_expr(&mut self, expr: &mut Expr) -> Type {
+        match expr {
+            Expr::Integer(_) => Type::Int,
+            Expr::Float(_) => Type::Float,
+            Expr::Boolean(_) => Type::Bool,
+            Expr::String(_) => Type::String,
+            Expr::NoneExpr(_) => Type::None,
+            Expr::Identifier(id) => self.lookup(id),
+            Expr::AssignmentExpr(assign) => self.check_assignment(assign),
+            Expr::DestructureExpr(destructure) => self.check_destructure(destructure),
+            Expr::ReassignmentExpr(reassign) => self.check_reassignment(reassign),
+            Expr::IndexExpr(index) => self.check_index(index),
+            Expr::MethodCallExpr(call) => self.check_call(call),
+            Expr::PrintExpr(print) => {
+                let value = self.check_expr(&mut print.arg);
+                if self.resolve(&value) == Type::None {
+                    self.error(print.arg.span(), "cannot print a value of type none");
+                } else {
+                    self.check_operator(OperatorUse {
+                        op: "print".to_string(),
+                        operand: value,
+                        binary: false,
+                        span: print.arg.span(),
+                    });
+                }
+                Type::None
+            }
+            Expr::IfExpr(if_expr) => self.check_if(if_expr),
+            Expr::RepExpr(rep) => self.check_rep(rep),
+            Expr::WhileExpr(w) => self.check_while(w),
+            Expr::ForExpr(f) => self.check_for(f),
+            Expr::RangeExpr(range) => self.check_range(range),
+            Expr::BreakExpr(b) => self.check_jump("break", &b.label, b.span, &mut b.target),
+            Expr::ContinueExpr(c) => self.check_jump("continue", &c.label, c.span, &mut c.target),
+            Expr::MatchExpr(m) => self.check_match(m),
+            Expr::ListExpr(list) => self.check_list(list),
+            Expr::TupleExpr(tuple) => self.check_tuple(tuple),
+            Expr::FieldAccessExpr(access) => self.check_field_access(access),
+            Expr::BinOp(binop) => self.check_binop(binop),
+            Expr::UnOp(unop) => self.check_unop(unop),
+            Expr::FunctionDef(func) => self.check_function(func),
+            Expr::LambdaExpr(lambda) => self.check_lambda(lambda),
+            // Declared before anything else
+            Expr::StructDef(_) | Expr::EnumDef(_) => Type::None,
+            Expr::ReturnExpr(ret) => self.check_return(ret),
+        }
+    }
+
+    fn check_assignment(&mut self, assign: &mut AssignmentExpr) -> Type {
+        let kind = if assign.const_var {
+            Kind::Val
+        } else {
+            Kind::Var
+        };
+        // A lambda may call itself through the binding it is assigned to, so
+        // that binding is declared before the lambda is checked
+        if let Expr::LambdaExpr(lambda) = assign.value.as_mut() {
+            let target = &mut assign.target;
+            let value = self.lambda_type(lambda);
+            if target.associated_type == Type::Infer {
+                target.associated_type = value;
+            } else if self.check_type_names(target.span, &target.associated_type) {
+                self.expect(lambda.span, &target.associated_type, &value);
+            } else {
+                target.associated_type = Type::Infer;
+            }
+            self.declare(&target.value, target.associated_type.clone(), kind);
+            self.check_lambda_body(lambda);
+            // A `var` is reached through its capture, and so may be assigned
+            // another function later, but a `val` is passed to itself
+            let name = &target.value.value;
+            if kind == Kind::Val && lambda.captures.iter().any(|c| &c.name == name) {
+                lambda.captures.retain(|c| &c.name != name);
+                lambda.recursive = Some(name.clone());
+            }
+            return Type::None;
+        }
+
+        let value = self.check_expr(&mut assign.value);
+        let target = &mut assign.target;
+        if target.associated_type == Type::Infer {
+            // Without an annotation the binding takes the type of its value,
+            // which may only be decided by later uses
+            let message = format!("`{}` cannot hold a value of type none", target.value.value);
+            if self.expect_value(assign.value.span(), &value, message) {
+                target.associated_type = value;
+            }
+        } else {
+            let annotation = target.associated_type.clone();
+            if self.check_type_names(target.span, &annotation) {
+                self.expect(assign.value.span(), &annotation, &value);
+            } else {
+                target.associated_type = Type::Infer;
+            }
+        }
+        let target = &assign.target;
+        self.declare(&target.value, target.associated_type.clone(), kind);
+        Type::None
+    }
+
+    fn check_destructure(&mut self, destructure: &mut DestructureExpr) -> Type {
+        let value = self.check_expr(&mut destructure.value);
+        // Targets without an annotation take the type of their element
+        let mut elems = vec![];
+        for target in destructure.targets.iter_mut() {
+            if target.associated_type == Type::Infer {
+                target.associated_type = self.fresh();
+            } else if !self.check_type_names(target.span, &target.associated_type) {
+                target.associated_type = Type::Infer;
+            }
+            elems.push(target.associated_type.clone());
+        }
+        let expected = Type::Tuple(elems.clone());
+        if !self.unify(&expected, &value) {
+            let (expected, found) = (self.resolve(&expected), self.resolve(&value));
+            self.error(
+                destructure.value.span(),
+                format!("mismatched types: expected {expected}, found {found}"),
+            );
+            // Targets the value did not decide are not reported again
+            for elem in &elems {
+                self.unify(elem, &Type::Infer);
+            }
+        }
+        let kind = if destructure.const_var {
+            Kind::Val
+        } else {
+            Kind::Var
+        };
+        for target in &destructure.targets {
+            self.declare(&target.value, target.associated_type.clone(), kind);
+        }
+        Type::None
+    }
+
+    fn check_reassignment(&mut self, reassign: &mut ReassignmentExpr) -> Type {
+        let target = self.check_expr(&mut reassign.target);
+        let value = self.check_expr(&mut reassign.value);
+        if !self.check_assignable(&reassign.target, "assign to") {
+            return Type::None;
+        }
+        // `x += e` stores what `x + e` would give
+        let value = match reassign.op.strip_suffix('=') {
+            Some(op) if !op.is_empty() => self.binop_type(op, target.clone(), value, reassign.span),
+            _ => value,
+        };
+        self.expect(reassign.value.span(), &target, &value);
+        Type::None
+    }
+
+    // Only a `var`, or a field or element of one, can be written to. `action`
+    // is what the write does, as in "cannot assign to `x`"
+    fn check_assignable(&mut self, target: &Expr, action: &str) -> bool {
+        let mut root = target;
+        while let Expr::FieldAccessExpr(FieldAccessExpr { object, .. })
+        | Expr::IndexExpr(IndexExpr { object, .. }) = root
+        {
+            root = object;
+        }
+        let name = match root {
+            Expr::Identifier(name) => name,
+            _ => {
+                self.error(target.span(), format!("cannot {action} this expression"));
+                return false;
+            }
+        };
+        let binding = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.bindings.get(&name.value))
+            .cloned();
+        let (kind, span) = match binding {
+            Some(Binding {
+                kind: Kind::Function,
+                ..
+            }) => {
+                let message = format!("cannot {action} function `{}`", name.value);
+                self.error(target.span(), message);
+                return false;
+            }
+            Some(Binding { kind, span, .. }) if kind.is_constant() => (kind, span),
+            // Unknown names were reported when the target was checked
+            _ => return true,
+        };
+        let name = &name.value;
+        let message = match kind {
+            Kind::Parameter => format!("cannot {action} parameter `{name}`"),
+            Kind::LoopTarget => format!("cannot {action} loop variable `{name}`"),
+            Kind::PatternBinding => {
+                format!("cannot {action} `{name}`, which is bound by a pattern")
+            }
+            _ => format!("cannot {action} `{name}`, which is a `val`"),
+        };
+        let note = if kind == Kind::Val {
+            format!("`{name}` is declared here; declare it with `var` to change it")
+        } else {
+            format!("`{name}` is declared here; copy it into a `var` to change it")
+        };
+        let error = TypeError::new(target.span(), message).with_note(span, note);
+        self.errors.push(error);
+        false
+    }
+
+    fn check_call(&mut self, call: &mut MethodCallExpr) -> Type {
+        let name = &call.method_name.value;
+        let declared = self.scopes.iter().any(|s| s.bindings.contains_key(name));
+        if !declared && BUILTINS.contains(&name.as_str()) {
+            call.builtin = true;
+            return self.check_builtin_call(call);
+        }
+        let mut arg_types = vec![];
+        for arg in call.args.iter_mut() {
+            let found = self.check_expr(arg);
+            let message = format!("`{name}` cannot take a value of type none");
+            let found = if self.expect_value(arg.span(), &found, message) {
+                found
+            } else {
+                Type::Infer
+            };
+            arg_types.push(found);
+        }
+        let name = &call.method_name;
+        let callee = self.lookup(name);
+        let (params, ret) = match self.resolve(&callee) {
+            Type::FunctionType(params, ret) => (params, ret.unwrap_or(Type::None)),
+            // A parameter that is called must be a function of its arguments
+            Type::Var(_) => {
+                let ret = self.fresh();
+                let ty = Type::FunctionType(arg_types, Box::new(Some(ret.clone())));
+                self.unify(&callee, &ty);
+                return ret;
+            }
+            Type::Infer => return Type::Infer,
+            other => {
+                self.error(
+                    name.span,
+                    format!("`{}` has type {other} and cannot be called", name.value),
+                );
+                return Type::Infer;
+            }
+        };
+        if self.check_arity(call, params.len()) {
+            let spans = call
+                .args
+                .iter()
+                .map(|arg| arg.span())
+                .collect::<Vec<Span>>();
+            for ((span, found), expected) in spans.into_iter().zip(&arg_types).zip(&params) {
+                self.expect(span, expected, found);
+            }
+        }
+        ret
+    }
+
+    fn check_arity(&mut self, call: &MethodCallExpr, params: usize) -> bool {
+        if params == call.args.len() {
+            return true;
+        }
+        let supplied = if call.args.len() == 1 { "was" } else { "were" };
+        let message = format!(
+            "`{}` takes {} but {} {supplied} supplied",
+            call.method_name.value,
+            plural(params, "argument"),
+            call.args.len()
+        );
+        self.error(call.span, message);
+        false
+    }
+
+    // The built-in list functions are generic over the elements of their
+    // lists. Each argument is checked knowing the ones before it, so that a
+    // lambda given to one has the type of the elements for its parameters
+    fn check_builtin_call(&mut self, call: &mut MethodCallExpr) -> Type {
+        let name = call.method_name.value.as_str();
+        let (a, b) = (self.fresh(), self.fresh());
+        let list = |t: &Type| Type::List(Box::new(t.clone()));
+        let func =
+            |params: Vec<Type>, ret: &Type| Type::FunctionType(params, Box::new(Some(ret.clone())));
+        let (params, ret) = match name {
+            "map" => (vec![list(&a), func(vec![a.clone()], &b)], list(&b)),
+            "filter" => (vec![list(&a), func(vec![a.clone()], &Type::Bool)], list(&a)),
+            "reduce" => (
+                vec![list(&a), func(vec![a.clone(), a.clone()], &a)],
+                a.clone(),
+            ),
+            "fold" => (
+                vec![list(&a), b.clone(), func(vec![b.clone(), a.clone()], &b)],
+                b.clone(),
+            ),
+            "any" | "all" => (
+                vec![list(&a), func(vec![a.clone()], &Type::Bool)],
+                Type::Bool,
+            ),
+            "zip" => (
+                vec![list(&a), list(&b)],
+                list(&Type::Tuple(vec![a.clone(), b.clone()])),
+            ),
+            "enumerate" => (
+                vec![list(&a)],
+                list(&Type::Tuple(vec![Type::Int, a.clone()])),
+            ),
+            "sorted" => (vec![list(&a)], list(&a)),
+            "push" => (vec![list(&a), a.clone()], Type::None),
+            "pop" => (vec![list(&a)], a.clone()),
+            // `sum`, `min` and `max`
+            _ => (vec![list(&a)], a.clone()),
+        };
+
+        let reported = self.errors.len();
+        for (arg, expected) in call.args.iter_mut().zip(&params) {
+            if let Expr::LambdaExpr(lambda) = arg {
+                let found = self.lambda_type(lambda);
+                self.expect(lambda.span, expected, &found);
+                self.check_lambda_body(lambda);
+            } else {
+                let found = self.check_expr(arg);
+                let message = format!("`{name}` cannot take a value of type none");
+                if self.expect_value(arg.span(), &found, message) {
+                    self.expect(arg.span(), expected, &found);
+                }
+            }
+        }
+        if !self.check_arity(call, params.len()) {
+            for arg in call.args.iter_mut().skip(params.len()) {
+                self.check_expr(arg);
+            }
+            return Type::Infer;
+        }
+
+        // `push` and `pop` change the list they are given, which is only
+        // worth checking once it is known to be a list
+        let action = match name {
+            "push" => Some("push to"),
+            "pop" => Some("pop from"),
+            _ => None,
+        };
+        if let Some(action) = action.filter(|_| self.errors.len() == reported) {
+            self.check_assignable(&call.args[0], action);
+        }
+        if matches!(name, "sum" | "min" | "max" | "sorted") {
+            self.check_operator(OperatorUse {
+                op: name.to_string(),
+                operand: a,
+                binary: false,
+                span: call.span,
+            });
+        }
+        if name == "map" && self.resolve(&b) == Type::None {
+            let message = "the function given to `map` must return a value";
+            self.error(call.args[1].span(), message);
+        }
+        ret
+    }
+
+    fn check_if(&mut self, if_expr: &mut IfExpr) -> Type {
+        let cond = self.check_expr(&mut if_expr.condition);
+        self.expect(if_expr.condition.span(), &Type::Bool, &cond);
+        let then_type = self.check_block(&mut if_expr.then_block);
+        let ty = match &mut if_expr.else_block {
+            Some(else_block) => {
+                let else_type = self.check_block(else_block);
+                // Branches that disagree make the `if` a statement; using it as a
+                // value is then caught wherever that value is expected
+                let before = self.subst.clone();
+                if self.unify(&then_type, &else_type) {
+                    then_type
+                } else {
+                    self.subst = before;
+                    Type::None
+                }
+            }
+            None => Type::None,
+        };
+        if_expr.ty = ty.clone();
+        ty
+    }
+
+    fn check_rep(&mut self, rep: &mut RepExpr) -> Type {
+        let count = self.check_expr(&mut rep.num_iterations);
+        self.expect(rep.num_iterations.span(), &Type::Int, &count);
+        self.check_loop_body(&mut rep.body, &rep.label, rep.span);
+        Type::None
+    }
+
+    // The targets are `val`s local to the body, holding each element of a
+    // list or each int of a range in turn
+    fn check_for(&mut self, f: &mut ForExpr) -> Type {
+        let iterable = self.check_expr(&mut f.iterable);
+        let elem = match self.resolve(&iterable) {
+            Type::Range => Type::Int,
+            Type::Infer => Type::Infer,
+            other => {
+                let elem = self.fresh();
+                if self.unify(&Type::List(Box::new(elem.clone())), &other) {
+                    elem
+                } else {
+                    self.error(f.iterable.span(), format!("cannot iterate over {other}"));
+                    Type::Infer
+                }
+            }
+        };
+
+        // As with `val (a, b) = pair`, targets without an annotation take
+        // the type of their element
+        let mut types = vec![];
+        for target in f.targets.iter_mut() {
+            if target.associated_type == Type::Infer {
+                target.associated_type = self.fresh();
+            } else if !self.check_type_names(target.span, &target.associated_type) {
+                target.associated_type = Type::Infer;
+            }
+            types.push(target.associated_type.clone());
+        }
+        let expected = match types.as_slice() {
+            [single] => single.clone(),
+            _ => Type::Tuple(types.clone()),
+        };
+        if !self.unify(&expected, &elem) {
+            let (expected, found) = (self.resolve(&expected), self.resolve(&elem));
+            self.error(
+                f.iterable.span(),
+                format!("mismatched types: expected elements of type {expected}, found {found}"),
+            );
+            for ty in &types {
+                self.unify(ty, &Type::Infer);
+            }
+        }
+
+        self.enter_loop(&f.label, f.span);
+        for target in &f.targets {
+            self.declare(
+                &target.value,
+                target.associated_type.clone(),
+                Kind::LoopTarget,
+            );
+        }
+        self.check_exprs(&mut f.body);
+        self.scopes.pop();
+        Type::None
+    }
+
+    fn check_range(&mut self, range: &mut RangeExpr) -> Type {
+        let bounds = [
+            Some(&mut range.start),
+            Some(&mut range.stop),
+            range.step.as_mut(),
+        ];
+        for bound in bounds.into_iter().flatten() {
+            let found = self.check_expr(bound);
+            self.expect(bound.span(), &Type::Int, &found);
+        }
+        Type::Range
+    }
+
+    fn check_while(&mut self, w: &mut WhileExpr) -> Type {
+        let cond = self.check_expr(&mut w.condition);
+        self.expect(w.condition.span(), &Type::Bool, &cond);
+        self.check_loop_body(&mut w.body, &w.label, w.span);
+        Type::None
+    }
+
+    fn check_match(&mut self, m: &mut MatchExpr) -> Type {
+        let scrutinee = self.check_expr(&mut m.scrutinee);
+        let mut arm_types = vec![];
+        for arm in m.arms.iter_mut() {
+            // Bindings are only visible in their own arm
+            self.scopes.push(Scope::default());
+            self.check_pattern(&mut arm.pattern, &scrutinee);
+            if let Some(guard) = &mut arm.guard {
+                let found = self.check_expr(guard);
+                self.expect(guard.span(), &Type::Bool, &found);
+            }
+            arm_types.push(self.check_exprs(&mut arm.body));
+            self.scopes.pop();
+        }
+        self.check_exhaustive(m, &scrutinee);
+
+        // As with `if`, arms that disagree make the match a statement
+        let before = self.subst.clone();
+        let ty = match arm_types.split_first() {
+            Some((first, rest)) if rest.iter().all(|t| self.unify(first, t)) => first.clone(),
+            _ => {
+                self.subst = before;
+                Type::None
+            }
+        };
+        m.ty = ty.clone();
+        ty
+    }
+
+    fn check_pattern(&mut self, pattern: &mut Pattern, expected: &Type) {
+        // A lone name is a variant whenever there is one by that name
+        if let Pattern::Binding(binding) = pattern {
+            if self.variants.contains_key(&binding.value.value) {
+                let (name, span) = (binding.value.clone(), binding.span);
+                *pattern = Pattern::Constructor(ConstructorPattern::new(name, vec![], span));
+            }
+        }
+        match pattern {
+            Pattern::Wildcard(_) => {}
+            Pattern::Literal(literal) => {
+                let found = self.check_expr(literal);
+                self.expect(literal.span(), expected, &found);
+            }
+            Pattern::Binding(binding) => {
+                binding.associated_type = expected.clone();
+                self.declare(&binding.value, expected.clone(), Kind::PatternBinding);
+            }
+            Pattern::Constructor(c) => {
+                let variant = &c.name.value;
+                let fields = match self.variants.get(variant) {
+                    Some(enum_name) => {
+                        c.enum_name = enum_name.clone();
+                        let fields = self.enums[enum_name]
+                            .iter()
+                            .find(|(v, _)| v == variant)
+                            .map(|(_, fields)| fields.clone())
+                            .unwrap();
+                        self.expect(c.span, expected, &Type::Named(enum_name.clone()));
+                        fields
+                    }
+                    None => {
+                        let message = format!("cannot find variant `{variant}` in this scope");
+                        self.error(c.name.span, message);
+                        vec![Type::Infer; c.args.len()]
+                    }
+                };
+                if fields.len() != c.args.len() {
+                    let message = format!(
+                        "`{variant}` has {} but the pattern has {}",
+                        plural(fields.len(), "field"),
+                        c.args.len()
+                    );
+                    self.error(c.span, message);
+                    for arg in c.args.iter_mut() {
+                        self.check_pattern(arg, &Type::Infer);
+                    }
+                } else {
+                    for (arg, field) in c.args.iter_mut().zip(&fields) {
+                        self.check_pattern(arg, field);
+                    }
+                }
+            }
+        }
+    }
+
+    // Reports a value that no arm matches. Guarded arms may not match
+    // anything, so only the others count
+    fn check_exhaustive(&mut self, m: &MatchExpr, scrutinee: &Type) {
+        if self.resolve(scrutinee) == Type::Infer {
+            return;
+        }
+        let rows = m
+            .arms
+            .iter()
+            .filter(|arm| arm.guard.is_none())
+            .map(|arm| vec![self.pat(&arm.pattern)])
+            .collect::<Vec<Vec<Pat>>>();
+        if let Some(missing) = self.missing(&rows, std::slice::from_ref(scrutinee)) {
+            let message = format!("non-exhaustive patterns: `{}` not covered", missing[0]);
+            self.error(m.scrutinee.span(), message);
+        }
+    }
+
+    fn pat(&self, pattern: &Pattern) -> Pat {
+        match pattern {
+            Pattern::Wildcard(_) | Pattern::Binding(_) => Pat::Any,
+            Pattern::Literal(literal) => {
+                let name = match literal {
+                    Expr::Integer(i) => i.value.to_string(),
+                    Expr::Float(f) => format!("{:?}", f.value),
+                    Expr::Boolean(b) => b.value.to_string(),
+                    Expr::String(s) => format!("{:?}", s.value),
+                    _ => return Pat::Any,
+                };
+                Pat::Ctor(name, vec![])
+            }
+            // An unknown variant was already reported
+            Pattern::Constructor(c) if c.enum_name.is_empty() => Pat::Any,
+            Pattern::Constructor(c) => Pat::Ctor(
+                c.name.value.clone(),
+                c.args.iter().map(|a| self.pat(a)).collect(),
+            ),
+        }
+    }
+
+    // Every constructor of `t` with the types of its arguments, or `None`
+    // when there are too many to list
+    fn constructors(&self, t: &Type) -> Option<Vec<(String, Vec<Type>)>> {
+        match self.resolve(t) {
+            Type::Bool => Some(vec![
+                ("true".to_string(), vec![]),
+                ("false".to_string(), vec![]),
+            ]),
+            Type::Named(name) => self.enums.get(&name).cloned(),
+            _ => None,
+        }
+    }
+
+    // A row of values, one of each of `types`, that none of `rows` match.
+    // This is the usefulness check from "Warnings for pattern matching"
+    // (Maranget, 2007), building the missing value as it goes
+    fn missing(&self, rows: &[Vec<Pat>], types: &[Type]) -> Option<Vec<Pat>> {
+        let Some((first, rest)) = types.split_first() else {
+            return if rows.is_empty() { Some(vec![]) } else { None };
+        };
+        let used = rows
+            .iter()
+            .filter_map(|row| match &row[0] {
+                Pat::Ctor(name, _) => Some(name.as_str()),
+                Pat::Any => None,
+            })
+            .collect::<HashSet<&str>>();
+        let all = self.constructors(first);
+
+        // With every constructor written out, the missing value is missing
+        // from the rows for one of them
+        if let Some(all) = &all {
+            if all.iter().all(|(name, _)| used.contains(name.as_str())) {
+                for (name, fields) in all {
+                    let rows = specialize(rows, name, fields.len());
+                    let types = fields.iter().chain(rest).cloned().collect::<Vec<Type>>();
+                    if let Some(mut args) = self.missing(&rows, &types) {
+                        let rest = args.split_off(fields.len());
+                        let mut row = vec![Pat::Ctor(name.clone(), args)];
+                        row.extend(rest);
+                        return Some(row);
+                    }
+                }
+                return None;
+            }
+        }
+
+        // Otherwise only the rows that match anything can cover the
+        // constructors that were left out
+        let defaults = rows
+            .iter()
+            .filter(|row| matches!(row[0], Pat::Any))
+            .map(|row| row[1..].to_vec())
+            .collect::<Vec<Vec<Pat>>>();
+        let rest = self.missing(&defaults, rest)?;
+        // A column nothing was written in is reported as a wildcard
+        let unused = all.filter(|_| !used.is_empty()).and_then(|all| {
+            all.into_iter()
+                .find(|(name, _)| !used.contains(name.as_str()))
+        });
+        let first = match unused {
+            Some((name, fields)) => Pat::Ctor(name, vec![Pat::Any; fields.len()]),
+            None => Pat::Any,
+        };
+        let mut row = vec![first];
+        row.extend(rest);
+        Some(row)
+    }
+
+    fn check_list(&mut self, list: &mut ListExpr) -> Type {
+        // An empty list has an element type that only its uses can decide
+        let elem_type = self.fresh();
+        for elem in list.elems.iter_mut() {
+            let found = self.check_expr(elem);
+            let message = "a list cannot hold a value of type none".to_string();
+            let found = if self.expect_value(elem.span(), &found, message) {
+                found
+            } else {
+                Type::Infer
+            };
+            self.expect(elem.span(), &elem_type, &found);
+        }
+        list.ty = Type::List(Box::new(elem_type));
+        list.ty.clone()
+    }
+
+    fn check_tuple(&mut self, tuple: &mut TupleExpr) -> Type {
+        let mut elems = vec![];
+        for elem in tuple.elems.iter_mut() {
+            let found = self.check_expr(elem);
+            let message = "a tuple cannot hold a value of type none".to_string();
+            let found = if self.expect_value(elem.span(), &found, message) {
+                found
+            } else {
+                Type::Infer
+            };
+            elems.push(found);
+        }
+        Type::Tuple(elems)
+    }
+
+    fn check_field_access(&mut self, access: &mut FieldAccessExpr) -> Type {
+        let object = self.check_expr(&mut access.object);
+        let object = self.resolve(&object);
+        let field = &access.field;
+        let message = match (&object, field.parse::<usize>()) {
+            (Type::Infer, _) => return Type::Infer,
+            (Type::Tuple(elems), Ok(index)) if index < elems.len() => return elems[index].clone(),
+            (Type::Tuple(_), Ok(index)) => {
+                format!("tuple index {index} is out of range for {object}")
+            }
+            (Type::Named(name), _) => match self.structs.get(name) {
+                Some(fields) => match fields.iter().find(|(f, _)| f == field) {
+                    Some((_, ty)) => return ty.clone(),
+                    None => format!("`{name}` has no field `{field}`"),
+                },
+                // The fields of a variant are only reached by matching on it
+                None if self.enums.contains_key(name) => {
+                    format!("`{name}` has no field `{field}`")
+                }
+                // Already reported where the name was written
+                None => return Type::Infer,
+            },
+            // Which fields there are depends on the type, so it cannot be
+            // left to later uses
+            (Type::Var(_), _) => {
+                format!(
+                    "the type of this value must be known to use `.{field}`, add a type annotation"
+                )
+            }
+            _ => format!("{object} has no field `{field}`"),
+        };
+        self.error(access.span, message);
+        Type::Infer
+    }
+
+    fn check_index(&mut self, index: &mut IndexExpr) -> Type {
+        let object = self.check_expr(&mut index.object);
+        let position = self.check_expr(&mut index.index);
+        self.expect(index.index.span(), &Type::Int, &position);
+        let elem = self.fresh();
+        if !self.unify(&object, &Type::List(Box::new(elem.clone()))) {
+            let object = self.resolve(&object);
+            self.error(index.span, format!("cannot index into {object}"));
+            return Type::Infer;
+        }
+        elem
+    }
+
+    fn check_binop(&mut self, binop: &mut BinOpExpr) -> Type {
+        let left = self.check_expr(&mut binop.left);
+        let right = self.check_expr(&mut binop.right);
+        self.binop_type(&binop.op, left, right, binop.span)
+    }
+
+    fn binop_type(&mut self, op: &str, left: Type, right: Type, span: Span) -> Type {
+        // An int meeting a float is promoted to float, once both sides are
+        // known. `==` and `!=` compare the promoted values
+        let promoted = matches!(
+            (self.resolve(&left), self.resolve(&right)),
+            (Type::Int, Type::Float) | (Type::Float, Type::Int)
+        );
+        let agree = match op {
+            "&&" | "||" => {
+                let left_ok = self.unify(&left, &Type::Bool);
+                self.unify(&right, &Type::Bool) && left_ok
+            }
+            _ if promoted => true,
+            _ => self.unify(&left, &right),
+        };
+        if !agree {
+            let (left, right) = (self.resolve(&left), self.resolve(&right));
+            self.error(span, format!("cannot apply `{op}` to {left} and {right}"));
+            return Type::Infer;
+        }
+        match op {
+            "==" | "!=" | "&&" | "||" => Type::Bool,
+            _ => {
+                self.check_operator(OperatorUse {
+                    op: op.to_string(),
+                    operand: left.clone(),
+                    binary: true,
+                    span,
+                });
+                match op {
+                    "<" | ">" | "<=" | ">=" => Type::Bool,
+                    _ if promoted => Type::Float,
+                    _ => left,
+                }
+            }
+        }
+    }
+
+    fn check_unop(&mut self, unop: &mut UnOpExpr) -> Type {
+        let arg = self.check_expr(&mut unop.arg);
+        if unop.op == "!" {
+            if !self.unify(&arg, &Type::Bool) {
+                let arg = self.resolve(&arg);
+                self.error(unop.span, format!("cannot apply `!` to {arg}"));
+                return Type::Infer;
+            }
+            return Type::Bool;
+        }
+        self.check_operator(OperatorUse {
+            op: unop.op.clone(),
+            operand: arg.clone(),
+            binary: false,
+            span: unop.span,
+        });
+        arg
+    }
+
+    fn check_function(&mut self, func: &mut FunctionDef) -> Type {
+        // Set when the signature was declared along with the rest of the block
+        let ret = func.return_type.clone().unwrap_or(Type::None);
+
+        // Arguments share the body's scope, as they do in C++
+        self.scopes.push(Scope {
+            function_boundary: true,
+            ..Scope::default()
+        });
+        for arg in &func.args {
+            self.declare(&arg.value, arg.associated_type.clone(), Kind::Parameter);
+        }
+        self.returns.push(ret.clone());
+        let body_type = self.check_exprs(&mut func.body);
+        self.returns.pop();
+        self.scopes.pop();
+
+        // The trailing expression is an implicit return unless the body
+        // already ends in an explicit one
+        match func.body.last() {
+            Some(Expr::ReturnExpr(_)) => {}
+            Some(last) => self.expect(last.span(), &ret, &body_type),
+            None => self.expect(func.span, &ret, &Type::None),
+        }
+        Type::None
+    }
+
+    fn check_lambda(&mut self, lambda: &mut LambdaExpr) -> Type {
+        let ty = self.lambda_type(lambda);
+        self.check_lambda_body(lambda);
+        ty
+    }
+
+    // Gives the parameters of `lambda` their types and it a return type to
+    // be decided by its body, which is checked separately
+    fn lambda_type(&mut self, lambda: &mut LambdaExpr) -> Type {
+        for param in lambda.params.iter_mut() {
+            if param.associated_type == Type::Infer {
+                param.associated_type = self.fresh();
+            } else if !self.check_type_names(param.span, &param.associated_type) {
+                param.associated_type = Type::Infer;
+            }
+        }
+        lambda.return_type = self.fresh();
+        let params = lambda.params.iter().map(|p| p.associated_type.clone());
+        Type::FunctionType(params.collect(), Box::new(Some(lambda.return_type.clone())))
+    }
+
+    fn check_lambda_body(&mut self, lambda: &mut LambdaExpr) {
+        let ret = lambda.return_type.clone();
+        self.scopes.push(Scope {
+            lambda: true,
+            ..Scope::default()
+        });
+        for param in &lambda.params {
+            self.declare(&param.value, param.associated_type.clone(), Kind::Parameter);
+        }
+        self.returns.push(ret.clone());
+        let body_type = self.check_exprs(&mut lambda.body);
+        self.returns.pop();
+        lambda.captures = self.scopes.pop().unwrap().captures;
+
+        match lambda.body.last() {
+            Some(Expr::ReturnExpr(_)) => {}
+            Some(last) => self.expect(last.span(), &ret, &body_type),
+            None => self.expect(lambda.span, &ret, &Type::None),
+        }
+    }
+
+    fn check_return(&mut self, ret: &mut ReturnExpr) -> Type {
+        let value = self.check_expr(&mut ret.value);
+        match self.returns.last().cloned() {
+            Some(expected) => self.expect(ret.value.span(), &expected, &value),
+            None => self.error(ret.span, "`return` outside of a function"),
+        }
+        // A `return` never produces a value where it appears, so it fits
+        // wherever it is used
+        self.fresh()
+    }
+}
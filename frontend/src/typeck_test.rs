@@ -0,0 +1,1342 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Expr, FunctionDef, LambdaExpr, Program, RepExpr, Span, StringLiteral};
+    use crate::parser::parse_program;
+    use crate::typeck::{check_program, TypeError};
+
+    fn check(input: &str) -> Result<(), Vec<TypeError>> {
+        let mut program = parse_program(input).unwrap().Program().unwrap();
+        check_program(&mut program)
+    }
+
+    // Error messages paired with the line and column they point at
+    fn errors(input: &str) -> Vec<(String, usize, usize)> {
+        check(input)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| (e.message, e.span.line, e.span.col))
+            .collect()
+    }
+
+    #[test]
+    fn test_well_typed_program() {
+        let program = r#"val x: int = 3
+val ys: list<int> = [x, 4 * x]
+def add(a: int, b: int) {
+return a + b
+}
+if x > 1 && x >= 0 {
+print(ys)
+}
+print("done")
+"#;
+        assert_eq!(check(program), Ok(()));
+    }
+
+    #[test]
+    fn test_assignment_mismatch() {
+        assert_eq!(
+            errors("val x: int = 3\nval y: list<int> = [4]\nval z: int = x + y\n"),
+            vec![("cannot apply `+` to int and list<int>".to_string(), 3, 14)]
+        );
+        assert_eq!(
+            errors("val s: string = 1\n"),
+            vec![(
+                "mismatched types: expected string, found int".to_string(),
+                1,
+                17
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unknown_identifier() {
+        assert_eq!(
+            errors("print(y)\n"),
+            vec![("cannot find `y` in this scope".to_string(), 1, 7)]
+        );
+        // Bindings do not outlive the block that declares them
+        assert_eq!(
+            errors("if 1 < 2 {\nval x: int = 1\n}\nprint(x)\n"),
+            vec![("cannot find `x` in this scope".to_string(), 4, 7)]
+        );
+    }
+
+    #[test]
+    fn test_condition_and_count_types() {
+        assert_eq!(
+            errors("if 1 {\nprint(1)\n}\n"),
+            vec![(
+                "mismatched types: expected bool, found int".to_string(),
+                1,
+                4
+            )]
+        );
+
+        let rep = Expr::RepExpr(RepExpr::new(
+            Expr::String(StringLiteral::new("three".to_string(), Span::default())),
+            vec![],
+            None,
+            Span::default(),
+        ));
+        assert_eq!(
+            check_program(&mut Program {
+                expressions: vec![rep]
+            })
+            .unwrap_err()[0]
+                .message,
+            "mismatched types: expected int, found string"
+        );
+    }
+
+    #[test]
+    fn test_boolean_operations() {
+        let program = r#"val ok: bool = true
+val both = ok && !false
+val either = 1 < 2 || both
+if !either {
+print(1)
+}
+"#;
+        assert_eq!(inferred(program), vec!["bool", "bool", "bool"]);
+        assert_eq!(
+            errors("val a = 1 && true\nval b = !2\nval c: bool = 0\n"),
+            vec![
+                ("cannot apply `&&` to int and bool".to_string(), 1, 9),
+                ("cannot apply `!` to int".to_string(), 2, 9),
+                (
+                    "mismatched types: expected bool, found int".to_string(),
+                    3,
+                    15
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_call_arity_and_arguments() {
+        let program = r#"def add(a: int, b: int) {
+a + b
+}
+add(1)
+add(1, "two")
+val x: string = add(1, 2)
+missing()
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                (
+                    "`add` takes 2 arguments but 1 was supplied".to_string(),
+                    4,
+                    1
+                ),
+                (
+                    "mismatched types: expected int, found string".to_string(),
+                    5,
+                    8
+                ),
+                (
+                    "mismatched types: expected string, found int".to_string(),
+                    6,
+                    17
+                ),
+                ("cannot find `missing` in this scope".to_string(), 7, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_functions_are_hoisted() {
+        assert_eq!(
+            check("print(double(2))\ndef double(n: int) {\nn * 2\n}\n"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_functions_do_not_capture_variables() {
+        let program = r#"val n: int = 5
+def f() {
+n
+}
+"#;
+        assert_eq!(
+            errors(program),
+            vec![(
+                "`n` is declared outside of this function and cannot be used inside it".to_string(),
+                3,
+                1
+            )]
+        );
+    }
+
+    // Types of the bindings declared at the top level of `input`
+    fn inferred(input: &str) -> Vec<String> {
+        let mut program = parse_program(input).unwrap().Program().unwrap();
+        check_program(&mut program).unwrap();
+        program
+            .expressions
+            .into_iter()
+            .filter_map(|e| e.AssignmentExpr())
+            .map(|a| a.target.associated_type.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_local_inference() {
+        let program = r#"val x = 3
+var xs = [1, x * 2]
+val s = "a"
+val b = x > 2
+val nested = [[], [x]]
+val empty: list<int> = []
+"#;
+        assert_eq!(
+            inferred(program),
+            vec![
+                "int",
+                "list<int>",
+                "string",
+                "bool",
+                "list<list<int>>",
+                "list<int>"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_inference() {
+        assert_eq!(
+            errors("var xs = []\n"),
+            vec![(
+                "cannot infer the type of `xs` from list<_>, add a type annotation".to_string(),
+                1,
+                5
+            )]
+        );
+        assert_eq!(
+            errors("val xs: list<int> = [\"a\"]\n"),
+            vec![(
+                "mismatched types: expected list<int>, found list<string>".to_string(),
+                1,
+                21
+            )]
+        );
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        let program = "val a: int = \"a\"\nval b: string = 2\nprint(c)\n";
+        assert_eq!(errors(program).len(), 3);
+    }
+
+    #[test]
+    fn test_print_needs_a_value() {
+        let program = r#"def log(s: string) {
+print(s)
+}
+print(print(1))
+print(log("a"))
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                ("cannot print a value of type none".to_string(), 4, 7),
+                ("cannot print a value of type none".to_string(), 5, 7),
+            ]
+        );
+
+        // A function has nothing to print, wherever it is in the value
+        let program = r#"struct Op { f: (int) -> int }
+enum Step { Done, Next(op: Op) }
+def show(x) {
+print([x])
+}
+val g = (x: int) => x
+print([g])
+print((1, Next(Op(g))))
+show(g)
+show(1)
+var hs = []
+print(hs)
+push(hs, g)
+"#;
+        let message = |ty: &str| format!("cannot print a value of type {ty}");
+        assert_eq!(
+            errors(program),
+            vec![
+                (message("list<(int) -> int>"), 7, 7),
+                (message("tuple<int, Step>"), 8, 7),
+                (message("list<(int) -> int>"), 9, 1),
+                (message("list<(int) -> int>"), 12, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_none_is_not_a_value() {
+        let program = r#"def log(s: string): none {
+print(s)
+}
+def wrap(x) {
+[x]
+}
+struct S { x: none }
+val a = log("a")
+val xs = [print(1)]
+val t = (1, log("b"))
+val w = wrap(print(2))
+var ys = [1]
+push(ys, print(3))
+val zs: list<none> = []
+val f: () -> none = () => print(4)
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                ("`none` is only allowed as a return type".to_string(), 7, 12),
+                ("`a` cannot hold a value of type none".to_string(), 8, 9),
+                ("a list cannot hold a value of type none".to_string(), 9, 11),
+                (
+                    "a tuple cannot hold a value of type none".to_string(),
+                    10,
+                    13
+                ),
+                (
+                    "`wrap` cannot take a value of type none".to_string(),
+                    11,
+                    14
+                ),
+                (
+                    "`push` cannot take a value of type none".to_string(),
+                    13,
+                    10
+                ),
+                ("`none` is only allowed as a return type".to_string(), 14, 5),
+            ]
+        );
+    }
+
+    // Parameter types of each top-level function after inference
+    fn parameters(input: &str) -> Vec<String> {
+        let mut program = parse_program(input).unwrap().Program().unwrap();
+        check_program(&mut program).unwrap();
+        program
+            .expressions
+            .into_iter()
+            .filter_map(|e| match e {
+                Expr::FunctionDef(FunctionDef { args, .. }) => Some(args),
+                _ => None,
+            })
+            .flatten()
+            .map(|a| a.associated_type.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parameter_inference() {
+        let program = r#"def inc(n) {
+n + 1
+}
+def greet(name, times: int) {
+print("hello " + name)
+times * 2
+}
+def scale(x) {
+x * 1.5
+}
+"#;
+        assert_eq!(parameters(program), vec!["int", "string", "int", "float"]);
+    }
+
+    #[test]
+    fn test_returns_are_inferred() {
+        let program = r#"def inc(n) {
+return n + 1
+}
+def half(x) {
+x / 2.0
+}
+val a = inc(1)
+val b = half(3.0)
+val c = [inc(a)]
+"#;
+        assert_eq!(inferred(program), vec!["int", "float", "list<int>"]);
+        assert_eq!(
+            errors("def f(n) {\nif n > 0 {\nreturn 1\n}\n\"none\"\n}\n"),
+            vec![(
+                "mismatched types: expected int, found string".to_string(),
+                5,
+                1
+            )]
+        );
+    }
+
+    #[test]
+    fn test_let_polymorphism() {
+        let program = r#"def id(x) {
+x
+}
+def add(a, b) {
+a + b
+}
+def twice(f, x) {
+f(f(x))
+}
+def inc(n: int) {
+n + 1
+}
+val n = id(1)
+val s = id("a")
+val xs = id([n])
+val total = add(1, 2)
+val name = add("a", "b")
+val four = twice(inc, 2)
+"#;
+        assert_eq!(
+            inferred(program),
+            vec!["int", "string", "list<int>", "int", "string", "int"]
+        );
+        // Generic parameters are left as type variables
+        assert_eq!(
+            parameters(program),
+            vec!["_", "_", "_", "(_) -> _", "_", "int"]
+        );
+    }
+
+    #[test]
+    fn test_generic_operators_are_checked_at_each_use() {
+        let program = r#"def add(a, b) {
+a + b
+}
+def sub(a, b) {
+a - b
+}
+val ok = add(1.5, 2.0)
+val bad = add([1], [2])
+val worse = sub("a", "b")
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                (
+                    "cannot apply `+` to list<int> and list<int>".to_string(),
+                    8,
+                    11
+                ),
+                ("cannot apply `-` to string and string".to_string(), 9, 13),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        let program = r#"def fact(n) {
+if n < 2 {
+return 1
+}
+return n * fact(n - 1)
+}
+def is_even(n) {
+if n == 0 {
+return 1 == 1
+}
+return is_odd(n - 1)
+}
+def is_odd(n) {
+if n == 0 {
+return 1 == 0
+}
+return is_even(n - 1)
+}
+val x = fact(5)
+val y = is_odd(x)
+"#;
+        assert_eq!(inferred(program), vec!["int", "bool"]);
+        assert_eq!(parameters(program), vec!["int", "int", "int"]);
+    }
+
+    #[test]
+    fn test_nested_functions_are_monomorphic() {
+        let program = r#"def outer(a: int) {
+def show(x) {
+print(x)
+}
+show(a)
+show("a")
+}
+"#;
+        assert_eq!(
+            errors(program),
+            vec![(
+                "mismatched types: expected int, found string".to_string(),
+                6,
+                6
+            )]
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_functions() {
+        assert_eq!(
+            errors("def empty() {\nreturn []\n}\n"),
+            vec![(
+                "cannot infer the return type of `empty` from list<_>, add a return type annotation"
+                    .to_string(),
+                1,
+                5
+            )]
+        );
+        let program = r#"def outer() {
+def f(x) {
+print(x)
+}
+1
+}
+"#;
+        assert_eq!(
+            errors(program),
+            vec![(
+                "cannot infer the type of parameter `x` from _, add a type annotation".to_string(),
+                2,
+                7
+            )]
+        );
+    }
+
+    #[test]
+    fn test_return_type_annotations() {
+        let program = r#"def empty(): list<int> {
+return []
+}
+def half(x: int): float {
+if x > 0 {
+return 0.5
+}
+1.0
+}
+val xs = empty()
+"#;
+        assert_eq!(inferred(program), vec!["list<int>"]);
+
+        let program = r#"def name(x: int): string {
+if x > 0 {
+return x
+}
+print(x)
+}
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                (
+                    "mismatched types: expected string, found int".to_string(),
+                    3,
+                    8
+                ),
+                (
+                    "mismatched types: expected string, found none".to_string(),
+                    5,
+                    1
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_return_types_are_filled_in() {
+        let mut program =
+            parse_program("def inc(n) {\nn + 1\n}\ndef log(s: string) {\nprint(s)\n}\n")
+                .unwrap()
+                .Program()
+                .unwrap();
+        check_program(&mut program).unwrap();
+        let returns = program
+            .expressions
+            .into_iter()
+            .filter_map(|e| e.FunctionDef())
+            .map(|f| f.return_type.unwrap().to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(returns, vec!["int", "none"]);
+    }
+
+    #[test]
+    fn test_ints_are_promoted_to_float() {
+        let program = r#"val n = 3
+val a = n * 0.5
+val b = 1.5 + n
+val c = n / 2
+val d = n < 2.5
+val e = n == 3.0
+"#;
+        assert_eq!(
+            inferred(program),
+            vec!["int", "float", "float", "int", "bool", "bool"]
+        );
+        assert_eq!(
+            errors("val s = \"a\" + 1.5\nval t: int = 1 * 2.0\n"),
+            vec![
+                ("cannot apply `+` to string and float".to_string(), 1, 9),
+                (
+                    "mismatched types: expected int, found float".to_string(),
+                    2,
+                    14
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tuples() {
+        let program = r#"def swap(p: tuple<int, string>) {
+(p.1, p.0)
+}
+val t = swap((1, "one"))
+val (s, n) = t
+val first = t.0
+val nested = ((1, 2.5), [true])
+val x = nested.0.1
+"#;
+        assert_eq!(
+            inferred(program),
+            vec![
+                "tuple<string, int>",
+                "string",
+                "tuple<tuple<int, float>, list<bool>>",
+                "float"
+            ]
+        );
+        let mut checked = parse_program(program).unwrap().Program().unwrap();
+        check_program(&mut checked).unwrap();
+        let targets = checked
+            .expressions
+            .into_iter()
+            .filter_map(|e| e.DestructureExpr())
+            .flat_map(|d| d.targets)
+            .map(|t| t.associated_type.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(targets, vec!["string", "int"]);
+
+        let program = r#"val t = (1, 2)
+val a = t.2
+val b = 3.0
+val (c, d, e) = t
+val (f: string, g) = t
+def first(p) {
+p.0
+}
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                (
+                    "tuple index 2 is out of range for tuple<int, int>".to_string(),
+                    2,
+                    9
+                ),
+                (
+                    "mismatched types: expected tuple<_, _, _>, found tuple<int, int>".to_string(),
+                    4,
+                    17
+                ),
+                (
+                    "mismatched types: expected tuple<string, _>, found tuple<int, int>"
+                        .to_string(),
+                    5,
+                    22
+                ),
+                (
+                    "the type of this value must be known to use `.0`, add a type annotation"
+                        .to_string(),
+                    7,
+                    1
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_structs() {
+        let program = r#"struct Line { start: Point, end: Point }
+struct Point { x: int, y: int }
+def length(l: Line) {
+l.end.x - l.start.x
+}
+var l = Line(Point(0, 0), Point(3, 4))
+val n = length(l)
+l.start.y = 2
+val same = l.start == Point(0, 2)
+"#;
+        assert_eq!(inferred(program), vec!["Line", "int", "bool"]);
+
+        let program = r#"struct Node { value: int, next: Node }
+struct Tree { children: list<Tree>, label: Label, label: int }
+var p = Node(1)
+val q: Missing = 1
+p.value = "one"
+val r = p.size
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                (
+                    "`Node` contains itself, so it would be infinitely large".to_string(),
+                    1,
+                    8
+                ),
+                ("cannot find type `Label` in this scope".to_string(), 2, 37),
+                (
+                    "field `label` is already declared in `Tree`".to_string(),
+                    2,
+                    51
+                ),
+                (
+                    "`Node` takes 2 arguments but 1 was supplied".to_string(),
+                    3,
+                    9
+                ),
+                ("cannot find type `Missing` in this scope".to_string(), 4, 5),
+                (
+                    "mismatched types: expected int, found string".to_string(),
+                    5,
+                    11
+                ),
+                ("`Node` has no field `size`".to_string(), 6, 9),
+            ]
+        );
+
+        // Every unknown name in an annotation is reported
+        let program = "val t: tuple<A, B> = (1, 2)\nval f: (C) -> D = (x: int) => x\n";
+        assert_eq!(
+            errors(program),
+            vec![
+                ("cannot find type `A` in this scope".to_string(), 1, 5),
+                ("cannot find type `B` in this scope".to_string(), 1, 5),
+                ("cannot find type `C` in this scope".to_string(), 2, 5),
+                ("cannot find type `D` in this scope".to_string(), 2, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enums_and_match() {
+        let program = r#"enum Shape { Circle(r: float), Rect(w: float, h: float), Empty }
+enum Tree { Leaf, Node(left: Tree, value: int, right: Tree) }
+def area(s) {
+match s {
+Circle(r) => r * r * 3.0
+Rect(w, h) if w == h => w * w
+Rect(w, h) => w * h
+Empty => 0.0
+}
+}
+def depth(t) {
+match t {
+Leaf => 0
+Node(Leaf, _, Leaf) => 1
+Node(l, _, r) => 1 + depth(l) + depth(r)
+}
+}
+val a = area(Rect(1.0, 2.0))
+val d = depth(Node(Leaf, 1, Leaf))
+val name = match d {
+0 => "none"
+n if n > 1 => "many"
+_ => "one"
+}
+val b = match a > 1.0 {
+true => Empty
+false => Circle(a)
+}
+"#;
+        assert_eq!(inferred(program), vec!["float", "int", "string", "Shape"]);
+
+        let program = r#"enum Shape { Circle(r: float), Rect(w: float, h: float), Empty }
+val s = Circle(1.0)
+val a = match s {
+Circle(r) => r
+Rect(w, _) if w > 0.0 => w
+}
+val b = match s {
+Circle(1, r) => r
+Square(x) => x
+_ => 0.0
+}
+val c = match 3 {
+"three" => 3
+}
+val d = match true {
+true => 1
+}
+val e = s.r
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                (
+                    "non-exhaustive patterns: `Rect(_, _)` not covered".to_string(),
+                    3,
+                    15
+                ),
+                (
+                    "`Circle` has 1 field but the pattern has 2".to_string(),
+                    8,
+                    1
+                ),
+                (
+                    "cannot find variant `Square` in this scope".to_string(),
+                    9,
+                    1
+                ),
+                (
+                    "non-exhaustive patterns: `_` not covered".to_string(),
+                    12,
+                    15
+                ),
+                (
+                    "mismatched types: expected int, found string".to_string(),
+                    13,
+                    1
+                ),
+                (
+                    "non-exhaustive patterns: `false` not covered".to_string(),
+                    15,
+                    15
+                ),
+                ("`Shape` has no field `r`".to_string(), 18, 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lambdas() {
+        let program = r#"def apply(f, x) {
+f(x)
+}
+def adder(n: int): (int) -> int {
+(x: int) => x + n
+}
+val k = 10
+var total = 0
+val twice = (x) => x * 2
+val add_k = (x: int) => x + k
+val shout = (s: string) => {
+print(s)
+s
+}
+val nested = (a: int) => (b: float) => a + b
+val f: (int, int) -> bool = (a, b) => a < b
+val n = apply(twice, 4)
+val add3 = adder(3)
+"#;
+        assert_eq!(
+            inferred(program),
+            vec![
+                "int",
+                "int",
+                "(int) -> int",
+                "(int) -> int",
+                "(string) -> string",
+                "(int) -> (float) -> float",
+                "(int, int) -> bool",
+                "int",
+                "(int) -> int",
+            ]
+        );
+
+        let program = r#"val twice = (x: int) => x * 2
+val a = twice("two")
+val b = twice(1, 2)
+val id = (x) => x
+val c: (int) -> string = twice
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                (
+                    "mismatched types: expected int, found string".to_string(),
+                    2,
+                    15
+                ),
+                (
+                    "`twice` takes 1 argument but 2 were supplied".to_string(),
+                    3,
+                    9
+                ),
+                (
+                    "cannot infer the type of `id` from (_) -> _, add a type annotation"
+                        .to_string(),
+                    4,
+                    5
+                ),
+                (
+                    "cannot infer the type of parameter `x` from _, add a type annotation"
+                        .to_string(),
+                    4,
+                    11
+                ),
+                (
+                    "mismatched types: expected (int) -> string, found (int) -> int".to_string(),
+                    5,
+                    26
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lambda_captures() {
+        let program = r#"val k = 1
+var total = 0
+def helper(x: int) {
+x
+}
+val f = (x: int) => {
+val g = (y: int) => helper(y) + k + x + total
+g(x)
+}
+"#;
+        let mut program = parse_program(program).unwrap().Program().unwrap();
+        check_program(&mut program).unwrap();
+        let f = program.expressions[3].clone().AssignmentExpr().unwrap();
+        let f = f.value.LambdaExpr().unwrap();
+        let captures = |lambda: &LambdaExpr| {
+            lambda
+                .captures
+                .iter()
+                .map(|c| (c.name.clone(), c.by_reference))
+                .collect::<Vec<_>>()
+        };
+        // `helper` is a function rather than a value, and `g` is local
+        assert_eq!(
+            captures(&f),
+            vec![("k".to_string(), false), ("total".to_string(), true)]
+        );
+        let g = f.body[0].clone().AssignmentExpr().unwrap();
+        let g = g.value.LambdaExpr().unwrap();
+        assert_eq!(
+            captures(&g),
+            vec![
+                ("k".to_string(), false),
+                ("x".to_string(), false),
+                ("total".to_string(), true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recursive_lambdas() {
+        let program = r#"val fact = (n) => {
+if n < 2 {
+return 1
+}
+n * fact(n - 1)
+}
+var fib = (n: int) => {
+if n < 2 {
+return n
+}
+fib(n - 1) + fib(n - 2)
+}
+val a = fact(5)
+"#;
+        assert_eq!(
+            inferred(program),
+            vec!["(int) -> int", "(int) -> int", "int"]
+        );
+
+        let mut program = parse_program(program).unwrap().Program().unwrap();
+        check_program(&mut program).unwrap();
+        let lambda = |i: usize| {
+            let assign = program.expressions[i].clone().AssignmentExpr().unwrap();
+            assign.value.LambdaExpr().unwrap()
+        };
+        // A `val` is handed itself, while a `var` is captured like any other
+        assert_eq!(lambda(0).recursive, Some("fact".to_string()));
+        assert!(lambda(0).captures.is_empty());
+        assert_eq!(lambda(1).recursive, None);
+        assert_eq!(lambda(1).captures.len(), 1);
+
+        // Only a lambda can refer to the binding it is assigned to
+        assert_eq!(
+            errors("val x = x + 1\nval f = (n: int) => f(n, 1)\n"),
+            vec![
+                ("cannot find `x` in this scope".to_string(), 1, 9),
+                (
+                    "cannot infer the type of `f` from (int) -> _, add a type annotation"
+                        .to_string(),
+                    2,
+                    5
+                ),
+                (
+                    "`f` takes 1 argument but 2 were supplied".to_string(),
+                    2,
+                    21
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_builtins() {
+        let program = r#"struct P { x: int, y: float }
+val xs = [3, 1, 2]
+val a = map(xs, (x) => P(x, 1.5))
+val b = map(a, (p) => p.y)
+val c = filter(xs, (x) => x > 1)
+val d = reduce(b, (l, r) => l + r)
+val e = fold(xs, "", (s, x) => s + "x")
+val f = any(a, (p) => p.x > 2)
+val g = zip(xs, a)
+val h = enumerate(b)
+val i = sum(b)
+val j = max(sorted(["b", "a"]))
+"#;
+        assert_eq!(
+            inferred(program),
+            vec![
+                "list<int>",
+                "list<P>",
+                "list<float>",
+                "list<int>",
+                "float",
+                "string",
+                "bool",
+                "list<tuple<int, P>>",
+                "list<tuple<int, float>>",
+                "float",
+                "string",
+            ]
+        );
+
+        let program = r#"val xs = [1, 2]
+val a = map(xs, (x) => x.y)
+val b = filter(xs, (x) => x)
+val c = sum(["a"])
+val d = sorted([true])
+val e = map(xs, (x) => print(x))
+val f = min(xs, 1)
+val g = map
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                ("int has no field `y`".to_string(), 2, 24),
+                (
+                    "mismatched types: expected bool, found int".to_string(),
+                    3,
+                    27
+                ),
+                ("cannot apply `sum` to string".to_string(), 4, 9),
+                ("cannot apply `sorted` to bool".to_string(), 5, 9),
+                (
+                    "the function given to `map` must return a value".to_string(),
+                    6,
+                    17
+                ),
+                (
+                    "`min` takes 1 argument but 2 were supplied".to_string(),
+                    7,
+                    9
+                ),
+                ("built-in `map` can only be called".to_string(), 8, 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reassignments() {
+        let program = r#"var xs = [1, 2]
+var f = 1.5
+var ps = [(1, "a")]
+xs[0] = 3
+xs[1] += xs[0] * 2
+f *= 2
+ps[0].1 += "b"
+val a = xs[1]
+val b = ps[0]
+"#;
+        assert_eq!(
+            inferred(program),
+            vec![
+                "list<int>",
+                "float",
+                "list<tuple<int, string>>",
+                "int",
+                "tuple<int, string>"
+            ]
+        );
+
+        let program = r#"struct P { x: int }
+val n = 1
+val p = P(1)
+var xs = [1]
+var m = 1
+def g(a: int) {
+a = 2
+}
+n = 2
+p.x += 1
+xs[0] = "one"
+xs[0] += 0.5
+g = 3
+xs.size = 1
+m[0] = 1
+xs["0"] = 1
+(m, 1).0 = 1
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                ("cannot assign to parameter `a`".to_string(), 7, 1),
+                ("cannot assign to `n`, which is a `val`".to_string(), 9, 1),
+                ("cannot assign to `p`, which is a `val`".to_string(), 10, 1),
+                (
+                    "mismatched types: expected int, found string".to_string(),
+                    11,
+                    9
+                ),
+                (
+                    "mismatched types: expected int, found float".to_string(),
+                    12,
+                    10
+                ),
+                ("cannot assign to function `g`".to_string(), 13, 1),
+                ("list<int> has no field `size`".to_string(), 14, 1),
+                ("cannot index into int".to_string(), 15, 1),
+                (
+                    "mismatched types: expected int, found string".to_string(),
+                    16,
+                    4
+                ),
+                ("cannot assign to this expression".to_string(), 17, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_val_immutability() {
+        let program = r#"var xs = []
+push(xs, 1)
+val last = pop(xs)
+"#;
+        assert_eq!(inferred(program), vec!["list<int>", "int"]);
+
+        let program = r#"val xs = [1, 2]
+def f(n: int) {
+var ys = [n]
+push(ys, n)
+n += 1
+}
+push(xs, 3)
+val x = pop(xs)
+xs[0] -= 1
+push(f, 1)
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                ("cannot assign to parameter `n`".to_string(), 5, 1),
+                ("cannot push to `xs`, which is a `val`".to_string(), 7, 6),
+                ("cannot pop from `xs`, which is a `val`".to_string(), 8, 13),
+                ("cannot assign to `xs`, which is a `val`".to_string(), 9, 1),
+                (
+                    "mismatched types: expected list<_>, found (int) -> none".to_string(),
+                    10,
+                    6
+                ),
+            ]
+        );
+
+        // Neither can be declared with `var`, so they are not told to be
+        let bound = r#"val g = (k: int) => {
+k = 1
+}
+match 3 {
+a => {
+a += 1
+}
+}
+"#;
+        assert_eq!(
+            errors(bound),
+            vec![
+                ("cannot assign to parameter `k`".to_string(), 2, 1),
+                (
+                    "cannot assign to `a`, which is bound by a pattern".to_string(),
+                    6,
+                    1
+                ),
+            ]
+        );
+
+        // Each write points back at the declaration
+        let errors = check(program).unwrap_err();
+        let notes = errors
+            .iter()
+            .map(|e| e.notes.iter().map(|(span, _)| (span.line, span.col)))
+            .map(|notes| notes.collect::<Vec<(usize, usize)>>())
+            .collect::<Vec<Vec<(usize, usize)>>>();
+        assert_eq!(
+            notes,
+            vec![
+                vec![(2, 7)],
+                vec![(1, 5)],
+                vec![(1, 5)],
+                vec![(1, 5)],
+                vec![]
+            ]
+        );
+        assert_eq!(
+            errors[1].render("test.bdl", program),
+            r#"test.bdl:7:6: error: cannot push to `xs`, which is a `val`
+  |
+7 | push(xs, 3)
+  |      ^^
+test.bdl:1:5: note: `xs` is declared here; declare it with `var` to change it
+  |
+1 | val xs = [1, 2]
+  |     ^^"#
+        );
+        assert!(errors[0].render("test.bdl", program).ends_with(
+            "note: `n` is declared here; copy it into a `var` to change it\n  \
+             |\n2 | def f(n: int) {\n  |       ^"
+        ));
+    }
+
+    #[test]
+    fn test_loop_jumps() {
+        let program = r#"var n = 10
+outer: while n > 0 {
+n -= 1
+rep n {
+if n == 3 {
+continue outer
+}
+val m = if n > 5 {
+break
+} else {
+n * 2
+}
+print(m + 1)
+break
+}
+}
+"#;
+        assert!(check(program).is_ok());
+
+        let program = r#"break
+while 1 {
+continue
+}
+def f() {
+continue
+}
+outer: rep 2 {
+def g() {
+break
+}
+val h = () => {
+break outer
+}
+inner: while true {
+break inner2
+}
+outer: rep 1 {
+continue outer
+}
+}
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                ("`break` outside of a loop".to_string(), 1, 1),
+                (
+                    "mismatched types: expected bool, found int".to_string(),
+                    2,
+                    7
+                ),
+                ("`continue` outside of a loop".to_string(), 6, 1),
+                ("`break` outside of a loop".to_string(), 10, 1),
+                ("`break` outside of a loop".to_string(), 13, 1),
+                (
+                    "no loop labelled `inner2` around this `break`".to_string(),
+                    16,
+                    7
+                ),
+                (
+                    "label `outer` is already used by an enclosing loop".to_string(),
+                    18,
+                    1
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_for_loops() {
+        let program = r#"def evens(n: int): range {
+return 0..n step 2
+}
+var total = 0
+for x in ["a", "b"] {
+print(x)
+}
+for i in evens(10) {
+total += i
+}
+for (i, s) in enumerate(["a"]) {
+total += i
+print(s)
+}
+rows: for (i, row: list<int>) in enumerate([[1]]) {
+for x in row {
+if x == 0 {
+continue rows
+}
+total += x
+}
+}
+"#;
+        assert!(check(program).is_ok());
+
+        let program = r#"for x in 3 {
+print(x)
+}
+for x in [1] {
+x = 2
+}
+for (a, b) in [1] {
+print(a)
+}
+for s: string in [1] {
+print(s)
+}
+val r = 0..true
+"#;
+        assert_eq!(
+            errors(program),
+            vec![
+                ("cannot iterate over int".to_string(), 1, 10),
+                ("cannot assign to loop variable `x`".to_string(), 5, 1),
+                (
+                    "mismatched types: expected elements of type tuple<_, _>, found int"
+                        .to_string(),
+                    7,
+                    15
+                ),
+                (
+                    "mismatched types: expected elements of type string, found int".to_string(),
+                    10,
+                    18
+                ),
+                (
+                    "mismatched types: expected int, found bool".to_string(),
+                    13,
+                    12
+                ),
+            ]
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use wasm_bindgen::prelude::*;
+use bdl_frontend::error::DEFAULT_FILE_NAME;
 use std::cell::RefCell;
 
 thread_local! {
@@ -27,15 +28,23 @@ pub fn compile_and_run(input: &str, stdin: &str) -> String {
     };
 
     // Extract Program from AstNode
-    let program = match ast {
+    let mut program = match ast {
         bdl_frontend::ast::AstNode::Program(prog) => prog,
         _ => {
             return "Expected Program AST node".to_string();
         }
     };
 
+    if let Err(errors) = bdl_frontend::typeck::check_program(&mut program) {
+        return errors
+            .iter()
+            .map(|e| format!("Type error: {}", e.render(DEFAULT_FILE_NAME, input)))
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
     // Generate C++ code
-    bdl_backend::codegen::generate(&program)
+    bdl_backend::codegen::generate(&program, &Default::default())
 }
 
 #[wasm_bindgen]